@@ -40,6 +40,25 @@ pub trait AppHandler {
     /// Called when a menu item is selected.
     #[allow(unused_variables)]
     fn command(&mut self, id: u32) {}
+
+    /// Called when the last window of the application has been closed, to decide
+    /// whether the application should quit. The default is to quit.
+    ///
+    /// This is currently only called on Linux.
+    fn should_quit_after_last_window(&mut self) -> bool {
+        true
+    }
+
+    /// Called when monitors are connected, disconnected or rearranged, or when the part
+    /// of a monitor which isn't covered by panels changes.
+    ///
+    /// Use [`Screen::get_monitors`] to get the new monitors, such as to move windows which
+    /// were on a monitor which has been disconnected.
+    ///
+    /// This is currently only called on Linux.
+    ///
+    /// [`Screen::get_monitors`]: crate::Screen::get_monitors
+    fn monitors_changed(&mut self) {}
 }
 
 /// The top level application object.
@@ -7,6 +7,8 @@ use crate::AppHandler;
 use super::clipboard::Clipboard;
 
 #[derive(Clone)]
+// There is only a single `Application`, so boxing the larger variant wouldn't save anything
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum Application {
     #[cfg(feature = "x11")]
     X11(x11::application::Application),
@@ -111,7 +113,9 @@ impl crate::platform::linux::ApplicationExt for crate::Application {
             #[cfg(feature = "x11")]
             Application::X11(it) => crate::Clipboard(Clipboard::X11(it.primary.clone())),
             #[cfg(feature = "wayland")]
-            Application::Wayland(_) => unimplemented!(),
+            Application::Wayland(it) => {
+                crate::Clipboard(Clipboard::Wayland(it.primary_clipboard()))
+            }
         }
     }
 }
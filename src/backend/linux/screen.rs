@@ -11,6 +11,6 @@ pub fn get_monitors() -> Vec<Monitor> {
         #[cfg(feature = "x11")]
         super::application::Application::X11(app) => x11::screen::get_monitors(app),
         #[cfg(feature = "wayland")]
-        super::application::Application::Wayland(_) => wayland::screen::get_monitors(),
+        super::application::Application::Wayland(app) => wayland::screen::get_monitors(app),
     }
 }
@@ -7,8 +7,8 @@ use crate::backend::wayland;
 #[cfg(feature = "x11")]
 use crate::backend::x11;
 use crate::{
-    text::Event, Cursor, CursorDesc, Error, FileDialogOptions, FileDialogToken, IdleToken, Scale,
-    TextFieldToken, TimerToken, WinHandler, WindowLevel, WindowState,
+    text::Event, Cursor, CursorDesc, Error, FileDialogOptions, FileDialogToken, IdleToken, Monitor,
+    ResizeEdge, Scale, TextFieldToken, TimerToken, WinHandler, WindowLevel, WindowState,
 };
 
 use super::{application::Application, menu::Menu};
@@ -30,8 +30,20 @@ impl CustomCursor {
             CustomCursor::Wayland(_) => panic!("Must use an X11 custom cursor here"),
         }
     }
+
+    /// The Wayland cursor, if this cursor was created by a Wayland window
+    #[cfg(feature = "wayland")]
+    pub(crate) fn wayland(&self) -> Option<&wayland::window::CustomCursor> {
+        match self {
+            #[cfg(feature = "x11")]
+            CustomCursor::X11(_) => None,
+            CustomCursor::Wayland(it) => Some(it),
+        }
+    }
 }
 
+// Builders are short-lived, so boxing the larger variant wouldn't save anything
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum WindowBuilder {
     #[cfg(feature = "x11")]
     X11(x11::window::WindowBuilder),
@@ -173,6 +185,16 @@ impl WindowBuilder {
         self
     }
 
+    pub fn fullscreen(mut self, monitor: Option<&Monitor>) -> Self {
+        self = match self {
+            #[cfg(feature = "x11")]
+            WindowBuilder::X11(builder) => WindowBuilder::X11(builder.fullscreen(monitor)),
+            #[cfg(feature = "wayland")]
+            WindowBuilder::Wayland(builder) => WindowBuilder::Wayland(builder.fullscreen(monitor)),
+        };
+        self
+    }
+
     pub fn build(self) -> Result<WindowHandle, Error> {
         match self {
             #[cfg(feature = "x11")]
@@ -257,14 +279,14 @@ impl From<x11::window::WindowHandle> for crate::WindowHandle {
 }
 
 impl WindowHandle {
-    // #[cfg(feature = "wayland")]
-    // /// Assume that this WindowHandle is from Wayland
-    // pub(crate) fn unwrap_wayland(&self) -> &wayland::window::WindowHandle {
-    //     match self {
-    //         WindowHandle::Wayland(it) => it,
-    //         _ => unreachable!("Must use a wayland window handle"),
-    //     }
-    // }
+    #[cfg(feature = "wayland")]
+    /// Assume that this WindowHandle is from Wayland
+    pub(crate) fn unwrap_wayland(&self) -> &wayland::window::WindowHandle {
+        match self {
+            WindowHandle::Wayland(it) => it,
+            _ => unreachable!("Must use a wayland window handle"),
+        }
+    }
     #[cfg(feature = "x11")]
     /// Assume that this WindowHandle is from X11
     pub(crate) fn unwrap_x11(&self) -> &x11::window::WindowHandle {
@@ -333,6 +355,20 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_fullscreen(&mut self, monitor: Option<&Monitor>) {
+        match self {
+            #[cfg(feature = "x11")]
+            WindowHandle::X11(handle) => {
+                handle.set_fullscreen(monitor);
+            }
+            #[cfg(feature = "wayland")]
+            WindowHandle::Wayland(handle) => {
+                handle.set_fullscreen(monitor);
+            }
+            WindowHandle::None => panic!("Used an uninitialised WindowHandle"),
+        }
+    }
+
     pub fn get_window_state(&self) -> WindowState {
         match self {
             #[cfg(feature = "x11")]
@@ -357,6 +393,20 @@ impl WindowHandle {
         }
     }
 
+    pub fn begin_resize(&self, edge: ResizeEdge) {
+        match self {
+            #[cfg(feature = "x11")]
+            WindowHandle::X11(handle) => {
+                handle.begin_resize(edge);
+            }
+            #[cfg(feature = "wayland")]
+            WindowHandle::Wayland(handle) => {
+                handle.begin_resize(edge);
+            }
+            WindowHandle::None => panic!("Used an uninitialised WindowHandle"),
+        }
+    }
+
     pub fn show_titlebar(&self, show_titlebar: bool) {
         match self {
             #[cfg(feature = "x11")]
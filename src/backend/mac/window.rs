@@ -63,9 +63,11 @@ use crate::pointer::{
 };
 use crate::region::Region;
 use crate::scale::Scale;
+use crate::screen::Monitor;
 use crate::text::{Event, InputHandler};
 use crate::window::{
-    FileDialogToken, IdleToken, TextFieldToken, TimerToken, WinHandler, WindowLevel, WindowState,
+    FileDialogToken, IdleToken, ResizeEdge, TextFieldToken, TimerToken, WinHandler, WindowLevel,
+    WindowState,
 };
 use crate::Error;
 
@@ -252,6 +254,10 @@ impl WindowBuilder {
         self
     }
 
+    pub fn fullscreen(self, _monitor: Option<&Monitor>) -> Self {
+        self.window_state(WindowState::Fullscreen)
+    }
+
     pub fn title(mut self, title: impl Into<String>) -> Self {
         self.title = title.into();
         self
@@ -1398,6 +1404,9 @@ impl WindowHandle {
             let window: id = msg_send![*self.nsview.load(), window];
             match (state, cur_state) {
                 (s1, s2) if s1 == s2 => (),
+                (WindowState::Fullscreen, _) => {
+                    tracing::warn!("Fullscreen windows are currently unimplemented for Mac.");
+                }
                 (WindowState::Minimized, _) => {
                     let () = msg_send![window, performMiniaturize: self];
                 }
@@ -1410,15 +1419,24 @@ impl WindowHandle {
                 (WindowState::Restored, WindowState::Minimized) => {
                     let () = msg_send![window, deminiaturize: self];
                 }
-                (WindowState::Restored, WindowState::Restored) => {} // Can't be reached
+                // Can't be reached, as we are never fullscreen
+                (WindowState::Restored, WindowState::Restored | WindowState::Fullscreen) => {}
             }
         }
     }
 
+    pub fn set_fullscreen(&mut self, _monitor: Option<&Monitor>) {
+        self.set_window_state(WindowState::Fullscreen);
+    }
+
     pub fn handle_titlebar(&self, _val: bool) {
         tracing::warn!("WindowHandle::handle_titlebar is currently unimplemented for Mac.");
     }
 
+    pub fn begin_resize(&self, _edge: ResizeEdge) {
+        tracing::warn!("WindowHandle::begin_resize is currently unimplemented for Mac.");
+    }
+
     pub fn resizable(&self, resizable: bool) {
         unsafe {
             let window: id = msg_send![*self.nsview.load(), window];
@@ -0,0 +1,583 @@
+// Copyright 2023 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Menus which glazier draws itself, for platforms without native menus.
+//!
+//! This module contains everything which doesn't depend on the platform: the contents of
+//! menus, how they are laid out and drawn into a buffer of pixels, and how they respond to
+//! the pointer and keyboard. The backends are responsible for showing the pixels in popup
+//! windows, and for sending them input.
+
+use std::fmt::Write;
+
+use crate::common_util::strip_access_key;
+use crate::hotkey::HotKey;
+use crate::keyboard::{KbKey, Modifiers};
+
+mod font;
+
+use font::{Glyph, ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// The width of the border around the menu
+const BORDER: u32 = 1;
+/// The space between the border and the first and last items
+const PADDING: u32 = 4;
+const ITEM_HEIGHT: u32 = 18;
+const SEPARATOR_HEIGHT: u32 = 9;
+/// The space to the left of the text of items, which holds the tick of selected items
+const LEFT_MARGIN: u32 = 20;
+/// The space to the right of items, which holds the arrow of submenus
+const RIGHT_MARGIN: u32 = 20;
+/// The minimum space between the text of an item and its hotkey
+const HOTKEY_GAP: u32 = 24;
+const MIN_WIDTH: u32 = 120;
+
+// The colours of the menu, as `0xAARRGGBB`
+const BACKGROUND: u32 = 0xfff6f5f4;
+const BORDER_COLOR: u32 = 0xffb6b6b3;
+const SEPARATOR_COLOR: u32 = 0xffdcdcda;
+const TEXT: u32 = 0xff2e3436;
+const DISABLED_TEXT: u32 = 0xff929595;
+const HOVERED_BACKGROUND: u32 = 0xff3584e4;
+const HOVERED_TEXT: u32 = 0xffffffff;
+
+/// The contents of a menu.
+#[derive(Default)]
+pub(crate) struct Menu {
+    items: Vec<MenuItem>,
+}
+
+enum MenuItem {
+    Command {
+        id: u32,
+        text: String,
+        hotkey: Option<String>,
+        selected: Option<bool>,
+        enabled: bool,
+    },
+    Submenu {
+        text: String,
+        menu: Menu,
+        enabled: bool,
+    },
+    Separator,
+}
+
+/// What the backend should do in response to input on a menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MenuAction {
+    /// The item with this id was chosen, so the menu should be closed and the command
+    /// sent to the window's handler
+    Command(u32),
+    /// The menu should be closed without choosing anything
+    Close,
+}
+
+impl Menu {
+    pub fn new() -> Menu {
+        Menu::default()
+    }
+
+    pub fn add_dropdown(&mut self, menu: Menu, text: &str, enabled: bool) {
+        self.items.push(MenuItem::Submenu {
+            text: strip_access_key(text),
+            menu,
+            enabled,
+        });
+    }
+
+    pub fn add_item(
+        &mut self,
+        id: u32,
+        text: &str,
+        key: Option<&HotKey>,
+        selected: Option<bool>,
+        enabled: bool,
+    ) {
+        self.items.push(MenuItem::Command {
+            id,
+            text: strip_access_key(text),
+            hotkey: key.map(hotkey_label),
+            selected,
+            enabled,
+        });
+    }
+
+    pub fn add_separator(&mut self) {
+        self.items.push(MenuItem::Separator);
+    }
+
+    /// The size of the menu in pixels, when drawn at `scale`.
+    pub fn size(&self, scale: u32) -> (u32, u32) {
+        let width = self
+            .items
+            .iter()
+            .map(|item| match item {
+                MenuItem::Command { text, hotkey, .. } => {
+                    let hotkey = hotkey
+                        .as_ref()
+                        .map_or(0, |hotkey| HOTKEY_GAP + text_width(hotkey));
+                    text_width(text) + hotkey
+                }
+                MenuItem::Submenu { text, .. } => text_width(text),
+                MenuItem::Separator => 0,
+            })
+            .map(|width| 2 * BORDER + LEFT_MARGIN + width + RIGHT_MARGIN)
+            .fold(MIN_WIDTH, u32::max);
+        let height: u32 = self.items.iter().map(MenuItem::height).sum();
+        (width * scale, (height + 2 * (BORDER + PADDING)) * scale)
+    }
+
+    /// The distance in pixels from the top of the menu to the top of the item at `index`.
+    pub fn item_top(&self, index: usize, scale: u32) -> u32 {
+        let above: u32 = self.items[..index].iter().map(MenuItem::height).sum();
+        (BORDER + PADDING + above) * scale
+    }
+
+    /// The index of the item which is `y` pixels from the top of the menu.
+    fn item_at(&self, y: f64, scale: u32) -> Option<usize> {
+        let mut top = f64::from((BORDER + PADDING) * scale);
+        for (index, item) in self.items.iter().enumerate() {
+            let bottom = top + f64::from(item.height() * scale);
+            if (top..bottom).contains(&y) {
+                return Some(index);
+            }
+            top = bottom;
+        }
+        None
+    }
+
+    /// The next item after `from` which can be hovered, wrapping around at the end.
+    fn next_item(&self, from: Option<usize>, forward: bool) -> Option<usize> {
+        let len = self.items.len();
+        (1..=len)
+            .map(|offset| match (from, forward) {
+                (Some(from), true) => (from + offset) % len,
+                (Some(from), false) => (from + len - offset) % len,
+                (None, true) => offset - 1,
+                (None, false) => len - offset,
+            })
+            .find(|&index| self.items[index].is_enabled())
+    }
+
+    /// The submenu at `index`, if that item is an enabled submenu.
+    fn submenu(&self, index: usize) -> Option<&Menu> {
+        match &self.items[index] {
+            MenuItem::Submenu {
+                menu,
+                enabled: true,
+                ..
+            } => Some(menu),
+            _ => None,
+        }
+    }
+
+    /// Draw the menu into `buffer`, which holds the `0xAARRGGBB` pixels of an image with the
+    /// size given by [`Menu::size`], in rows from top to bottom.
+    pub fn draw(&self, buffer: &mut [u32], scale: u32, hovered: Option<usize>) {
+        let (width, height) = self.size(scale);
+        let mut canvas = Canvas {
+            buffer,
+            width,
+            scale,
+        };
+        canvas.fill(0, 0, width, height, BORDER_COLOR);
+        let inner = BORDER * scale;
+        canvas.fill(
+            inner,
+            inner,
+            width - 2 * inner,
+            height - 2 * inner,
+            BACKGROUND,
+        );
+
+        let mut top = (BORDER + PADDING) * scale;
+        // The right edge of the space for the contents of items
+        let right = width - (BORDER + RIGHT_MARGIN) * scale;
+        let text_top = (ITEM_HEIGHT - GLYPH_HEIGHT) / 2 * scale;
+        for (index, item) in self.items.iter().enumerate() {
+            let item_height = item.height() * scale;
+            let color = if !item.is_enabled() {
+                DISABLED_TEXT
+            } else if hovered == Some(index) {
+                canvas.fill(
+                    inner,
+                    top,
+                    width - 2 * inner,
+                    item_height,
+                    HOVERED_BACKGROUND,
+                );
+                HOVERED_TEXT
+            } else {
+                TEXT
+            };
+            let left = (BORDER + LEFT_MARGIN) * scale;
+            match item {
+                MenuItem::Command {
+                    text,
+                    hotkey,
+                    selected,
+                    ..
+                } => {
+                    if *selected == Some(true) {
+                        let x = (BORDER + (LEFT_MARGIN - GLYPH_WIDTH) / 2) * scale;
+                        canvas.glyph(x, top + text_top, font::CHECK, color);
+                    }
+                    canvas.text(left, top + text_top, text, color);
+                    if let Some(hotkey) = hotkey {
+                        let x = right - text_width(hotkey) * scale;
+                        canvas.text(x, top + text_top, hotkey, color);
+                    }
+                }
+                MenuItem::Submenu { text, .. } => {
+                    canvas.text(left, top + text_top, text, color);
+                    let x = right + (RIGHT_MARGIN - GLYPH_WIDTH) / 2 * scale;
+                    canvas.glyph(x, top + text_top, font::ARROW, color);
+                }
+                MenuItem::Separator => {
+                    let margin = (BORDER + PADDING) * scale;
+                    canvas.fill(
+                        margin,
+                        top + item_height / 2,
+                        width - 2 * margin,
+                        scale,
+                        SEPARATOR_COLOR,
+                    );
+                }
+            }
+            top += item_height;
+        }
+    }
+}
+
+impl MenuItem {
+    fn height(&self) -> u32 {
+        match self {
+            MenuItem::Separator => SEPARATOR_HEIGHT,
+            _ => ITEM_HEIGHT,
+        }
+    }
+
+    /// Whether the item can be hovered and chosen
+    fn is_enabled(&self) -> bool {
+        match self {
+            MenuItem::Command { enabled, .. } | MenuItem::Submenu { enabled, .. } => *enabled,
+            MenuItem::Separator => false,
+        }
+    }
+}
+
+/// A menu which is open on screen, along with its open submenus.
+pub(crate) struct MenuTracker {
+    root: Menu,
+    /// The open menus, starting with `root`
+    levels: Vec<MenuLevel>,
+    /// Whether releasing a button should choose the hovered item.
+    ///
+    /// This is only the case once the pointer has moved or been pressed on the menu, so
+    /// that releasing the button which opened the menu doesn't immediately choose an item.
+    armed: bool,
+}
+
+/// The state of one of the open menus of a [`MenuTracker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MenuLevel {
+    /// The index of the item in the previous menu which this is the submenu of, which is
+    /// `None` for the root menu
+    pub parent: Option<usize>,
+    /// The item under the pointer, or selected with the keyboard
+    pub hovered: Option<usize>,
+}
+
+impl MenuTracker {
+    pub fn new(root: Menu) -> MenuTracker {
+        MenuTracker {
+            root,
+            levels: vec![MenuLevel {
+                parent: None,
+                hovered: None,
+            }],
+            armed: false,
+        }
+    }
+
+    /// The states of the open menus, from the root menu to the deepest submenu
+    pub fn levels(&self) -> &[MenuLevel] {
+        &self.levels
+    }
+
+    /// The contents of the open menu at `level`
+    pub fn menu(&self, level: usize) -> &Menu {
+        let mut menu = &self.root;
+        for level in &self.levels[1..=level] {
+            let parent = level.parent.expect("only the root menu has no parent");
+            menu = menu.submenu(parent).expect("open menus must be submenus");
+        }
+        menu
+    }
+
+    /// Draw the open menu at `level`, as described in [`Menu::draw`]
+    pub fn draw(&self, level: usize, buffer: &mut [u32], scale: u32) {
+        self.menu(level)
+            .draw(buffer, scale, self.levels[level].hovered);
+    }
+
+    /// The pointer moved to `y` pixels from the top of the menu at `level`, or outside
+    /// every menu if `level` is `None`
+    pub fn pointer_moved(&mut self, level: Option<usize>, y: f64, scale: u32) {
+        let Some(level) = level else {
+            // Keep the parents of open submenus highlighted
+            self.levels.last_mut().unwrap().hovered = None;
+            return;
+        };
+        self.armed = true;
+        let menu = self.menu(level);
+        let hovered = menu
+            .item_at(y, scale)
+            .filter(|&index| menu.items[index].is_enabled());
+        let has_submenu = hovered.and_then(|index| menu.submenu(index)).is_some();
+        self.levels[level].hovered = hovered;
+        if self.levels.get(level + 1).map(|it| it.parent) != Some(hovered) {
+            self.levels.truncate(level + 1);
+            if has_submenu {
+                self.levels.push(MenuLevel {
+                    parent: hovered,
+                    hovered: None,
+                });
+            }
+        }
+    }
+
+    /// A button was pressed on the menu at `level`, or outside every menu if `level` is `None`
+    pub fn button_pressed(&mut self, level: Option<usize>) -> Option<MenuAction> {
+        self.armed = true;
+        level.is_none().then_some(MenuAction::Close)
+    }
+
+    /// A button was released at `y` pixels from the top of the menu at `level`, or outside
+    /// every menu if `level` is `None`
+    pub fn button_released(
+        &mut self,
+        level: Option<usize>,
+        y: f64,
+        scale: u32,
+    ) -> Option<MenuAction> {
+        if !std::mem::replace(&mut self.armed, true) {
+            return None;
+        }
+        let level = level?;
+        let menu = self.menu(level);
+        match menu.items.get(menu.item_at(y, scale)?)? {
+            MenuItem::Command {
+                id, enabled: true, ..
+            } => Some(MenuAction::Command(*id)),
+            _ => None,
+        }
+    }
+
+    /// A key was pressed while the menu was open
+    pub fn key_down(&mut self, key: &KbKey) -> Option<MenuAction> {
+        let depth = self.levels.len() - 1;
+        let menu = self.menu(depth);
+        let hovered = self.levels[depth].hovered;
+        let next = match key {
+            KbKey::ArrowDown => menu.next_item(hovered, true),
+            KbKey::ArrowUp => menu.next_item(hovered, false),
+            KbKey::Home => menu.next_item(None, true),
+            KbKey::End => menu.next_item(None, false),
+            KbKey::ArrowRight => {
+                self.open_submenu();
+                return None;
+            }
+            KbKey::ArrowLeft | KbKey::Escape if depth > 0 => {
+                self.levels.pop();
+                return None;
+            }
+            KbKey::Escape => return Some(MenuAction::Close),
+            KbKey::Enter => return self.choose_hovered(),
+            KbKey::Character(c) if c == " " => return self.choose_hovered(),
+            _ => return None,
+        };
+        self.levels[depth].hovered = next;
+        None
+    }
+
+    /// Choose the hovered item of the deepest menu, opening it if it is a submenu
+    fn choose_hovered(&mut self) -> Option<MenuAction> {
+        let depth = self.levels.len() - 1;
+        let menu = self.menu(depth);
+        match self.levels[depth].hovered.map(|index| &menu.items[index]) {
+            Some(MenuItem::Command {
+                id, enabled: true, ..
+            }) => Some(MenuAction::Command(*id)),
+            Some(MenuItem::Submenu { .. }) => {
+                self.open_submenu();
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Open the submenu under the hovered item of the deepest menu, and hover its first item
+    fn open_submenu(&mut self) {
+        let depth = self.levels.len() - 1;
+        let Some(parent) = self.levels[depth].hovered else {
+            return;
+        };
+        if let Some(submenu) = self.menu(depth).submenu(parent) {
+            let hovered = submenu.next_item(None, true);
+            self.levels.push(MenuLevel {
+                parent: Some(parent),
+                hovered,
+            });
+        }
+    }
+}
+
+/// The width of `text` in unscaled pixels
+fn text_width(text: &str) -> u32 {
+    match text.chars().count() as u32 {
+        0 => 0,
+        len => len * ADVANCE - (ADVANCE - GLYPH_WIDTH),
+    }
+}
+
+/// The label shown next to an item for its hotkey, such as `Ctrl+Shift+S`
+fn hotkey_label(key: &HotKey) -> String {
+    let mut label = String::new();
+    let mods: Modifiers = key.mods.into();
+    if mods.ctrl() {
+        label.push_str("Ctrl+");
+    }
+    if mods.shift() {
+        label.push_str("Shift+");
+    }
+    if mods.alt() {
+        label.push_str("Alt+");
+    }
+    if mods.meta() {
+        label.push_str("Super+");
+    }
+    match &key.key {
+        KbKey::Character(c) => match c.as_str() {
+            "+" => label.push_str("Plus"),
+            "-" => label.push_str("Minus"),
+            " " => label.push_str("Space"),
+            _ => label.extend(c.chars().flat_map(|c| c.to_uppercase())),
+        },
+        KbKey::Escape => label.push_str("Esc"),
+        KbKey::Delete => label.push_str("Del"),
+        KbKey::Insert => label.push_str("Ins"),
+        KbKey::PageUp => label.push_str("PgUp"),
+        KbKey::PageDown => label.push_str("PgDn"),
+        KbKey::ArrowLeft => label.push_str("Left"),
+        KbKey::ArrowRight => label.push_str("Right"),
+        KbKey::ArrowUp => label.push_str("Up"),
+        KbKey::ArrowDown => label.push_str("Down"),
+        other => {
+            let _ = write!(label, "{other}");
+        }
+    }
+    label
+}
+
+/// A buffer of pixels which is drawn on in unscaled pixels
+struct Canvas<'a> {
+    buffer: &'a mut [u32],
+    width: u32,
+    scale: u32,
+}
+
+impl Canvas<'_> {
+    /// Fill a rectangle, given in pixels
+    fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, color: u32) {
+        for row in y..y + height {
+            let start = (row * self.width + x) as usize;
+            self.buffer[start..start + width as usize].fill(color);
+        }
+    }
+
+    /// Draw `text` with its top left corner at the given position in pixels
+    fn text(&mut self, x: u32, y: u32, text: &str, color: u32) {
+        for (i, c) in text.chars().enumerate() {
+            let x = x + i as u32 * ADVANCE * self.scale;
+            if x + GLYPH_WIDTH * self.scale > self.width {
+                break;
+            }
+            self.glyph(x, y, font::glyph(c), color);
+        }
+    }
+
+    fn glyph(&mut self, x: u32, y: u32, glyph: Glyph, color: u32) {
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                    self.fill(
+                        x + column * self.scale,
+                        y + row as u32 * self.scale,
+                        self.scale,
+                        self.scale,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hotkey::SysMods;
+
+    fn menu() -> Menu {
+        let mut submenu = Menu::new();
+        submenu.add_item(10, "Inner", None, None, true);
+        let mut menu = Menu::new();
+        menu.add_item(1, "&Open", None, None, true);
+        menu.add_separator();
+        menu.add_item(2, "Disabled", None, None, false);
+        menu.add_dropdown(submenu, "More", true);
+        menu
+    }
+
+    #[test]
+    fn keyboard_navigation() {
+        let mut tracker = MenuTracker::new(menu());
+        assert_eq!(tracker.key_down(&KbKey::ArrowDown), None);
+        assert_eq!(tracker.levels()[0].hovered, Some(0));
+        // The separator and the disabled item are skipped
+        tracker.key_down(&KbKey::ArrowDown);
+        assert_eq!(tracker.levels()[0].hovered, Some(3));
+        tracker.key_down(&KbKey::ArrowRight);
+        assert_eq!(tracker.levels().len(), 2);
+        assert_eq!(tracker.levels()[1].hovered, Some(0));
+        assert_eq!(
+            tracker.key_down(&KbKey::Enter),
+            Some(MenuAction::Command(10))
+        );
+        tracker.key_down(&KbKey::Escape);
+        assert_eq!(tracker.levels().len(), 1);
+        assert_eq!(tracker.key_down(&KbKey::Escape), Some(MenuAction::Close));
+    }
+
+    #[test]
+    fn hotkey_labels() {
+        assert_eq!(
+            hotkey_label(&HotKey::new(SysMods::CmdShift, "S")),
+            "Ctrl+Shift+S"
+        );
+        assert_eq!(hotkey_label(&HotKey::new(None, KbKey::F5)), "F5");
+    }
+}
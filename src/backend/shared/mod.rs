@@ -24,6 +24,7 @@ cfg_if::cfg_if! {
     if #[cfg(all(any(target_os = "freebsd", target_os = "linux"), any(feature = "x11", feature = "wayland")))] {
         pub(crate) mod xkb;
         pub(crate) mod linux;
+        pub(crate) mod menu;
     }
 }
 cfg_if::cfg_if! {
@@ -35,22 +35,34 @@ use smithay_client_toolkit::{
         calloop_wayland_source::WaylandSource,
         client::{
             globals::{registry_queue_init, BindError},
-            protocol::wl_compositor,
+            protocol::{wl_compositor, wl_shm},
             Connection, QueueHandle,
         },
+        protocols::wp::{
+            fractional_scale::v1::client::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+            viewporter::client::wp_viewporter::WpViewporter,
+        },
     },
     registry::RegistryState,
     seat::SeatState,
     shell::xdg::XdgShell,
+    shm::Shm,
+    subcompositor::SubcompositorState,
 };
 
-use super::{clipboard, error::Error, ActiveAction, IdleAction, WaylandState};
+use super::{
+    clipboard::{self, DataDeviceManagerData, PrimarySelectionManagerData, Selection},
+    cursor::{CursorShapeManagerData, CursorThemes},
+    error::Error,
+    window::{FractionalScaleManagerData, ViewporterData},
+    ActiveAction, IdleAction, WaylandState,
+};
 use crate::{
     backend::{
         shared::{linux, xkb::Context},
-        wayland::input::TextInputManagerData,
+        wayland::input::{TabletManagerData, TextInputManagerData},
     },
-    AppHandler,
+    AppHandler, Monitor,
 };
 
 #[derive(Clone)]
@@ -60,6 +72,10 @@ pub struct Application {
     // The inner is taken in `run`
     state: Arc<RwLock<Option<WaylandState>>>,
     pub(super) compositor: wl_compositor::WlCompositor,
+    pub(super) shm: wl_shm::WlShm,
+    pub(super) subcompositor: Option<Arc<SubcompositorState>>,
+    pub(super) fractional_scale_manager: Option<WpFractionalScaleManagerV1>,
+    pub(super) viewporter: Option<WpViewporter>,
     pub(super) wayland_queue: QueueHandle<WaylandState>,
     pub(super) xdg_shell: Weak<XdgShell>,
     loop_signal: LoopSignal,
@@ -67,6 +83,9 @@ pub struct Application {
     pub(super) idle_sender: Sender<IdleAction>,
     pub(super) loop_sender: channel::Sender<ActiveAction>,
     pub(super) raw_display_handle: *mut c_void,
+    clipboard: clipboard::Clipboard,
+    primary_clipboard: clipboard::Clipboard,
+    pub(super) monitors: Arc<RwLock<Vec<Monitor>>>,
 }
 
 impl Application {
@@ -74,16 +93,12 @@ impl Application {
         tracing::info!("wayland application initiated");
 
         let conn = Connection::connect_to_env()?;
-        let (globals, event_queue) = registry_queue_init::<WaylandState>(&conn).unwrap();
+        let (globals, mut event_queue) = registry_queue_init::<WaylandState>(&conn).unwrap();
         let qh = event_queue.handle();
         let event_loop: EventLoop<WaylandState> = EventLoop::try_new()?;
         let loop_handle = event_loop.handle();
         let loop_signal = event_loop.get_signal();
 
-        WaylandSource::new(conn.clone(), event_queue)
-            .insert(loop_handle.clone())
-            .unwrap();
-
         // We use a channel to delay events until outside of the user's handler
         // This allows the handler to be used in response to methods
         let (loop_sender, active_source) = channel::channel();
@@ -114,17 +129,94 @@ impl Application {
             },
             |it| Ok(Some(it)),
         )?;
+        let tablet_manager_global = globals.bind(&qh, 1..=1, TabletManagerData).map_or_else(
+            |err| match err {
+                e @ BindError::UnsupportedVersion => Err(e),
+                BindError::NotPresent => Ok(None),
+            },
+            |it| Ok(Some(it)),
+        )?;
+        let data_device_manager_global = globals
+            .bind(&qh, 1..=3, DataDeviceManagerData)
+            .map_or_else(
+                |err| match err {
+                    e @ BindError::UnsupportedVersion => Err(e),
+                    BindError::NotPresent => Ok(None),
+                },
+                |it| Ok(Some(it)),
+            )?;
+        let primary_selection_manager_global = globals
+            .bind(&qh, 1..=1, PrimarySelectionManagerData)
+            .map_or_else(
+                |err| match err {
+                    e @ BindError::UnsupportedVersion => Err(e),
+                    BindError::NotPresent => Ok(None),
+                },
+                |it| Ok(Some(it)),
+            )?;
+        let cursor_shape_manager_global = globals
+            .bind(&qh, 1..=1, CursorShapeManagerData)
+            .map_or_else(
+                |err| match err {
+                    e @ BindError::UnsupportedVersion => Err(e),
+                    BindError::NotPresent => Ok(None),
+                },
+                |it| Ok(Some(it)),
+            )?;
+        let fractional_scale_manager = globals
+            .bind(&qh, 1..=1, FractionalScaleManagerData)
+            .map_or_else(
+                |err| match err {
+                    e @ BindError::UnsupportedVersion => Err(e),
+                    BindError::NotPresent => Ok(None),
+                },
+                |it| Ok(Some(it)),
+            )?;
+        let viewporter = globals.bind(&qh, 1..=1, ViewporterData).map_or_else(
+            |err| match err {
+                e @ BindError::UnsupportedVersion => Err(e),
+                BindError::NotPresent => Ok(None),
+            },
+            |it| Ok(Some(it)),
+        )?;
+        let subcompositor = SubcompositorState::bind(compositor.clone(), &globals, &qh)
+            .map_or_else(
+                |err| match err {
+                    e @ BindError::UnsupportedVersion => Err(e),
+                    BindError::NotPresent => Ok(None),
+                },
+                |it| Ok(Some(Arc::new(it))),
+            )?;
+        let shm = Shm::bind(&globals, &qh)?;
+        let wl_shm = shm.wl_shm().clone();
+        let cursor_themes = CursorThemes::new(conn.clone(), shm.wl_shm().clone());
+        let clipboard = clipboard::Clipboard::new(
+            Selection::Clipboard,
+            data_device_manager_global.is_some(),
+            conn.clone(),
+            loop_sender.clone(),
+        );
+        let primary_clipboard = clipboard::Clipboard::new(
+            Selection::Primary,
+            primary_selection_manager_global.is_some(),
+            conn.clone(),
+            loop_sender.clone(),
+        );
+
+        let monitors = Arc::new(RwLock::new(Vec::new()));
 
         let mut state = WaylandState {
             registry_state: RegistryState::new(&globals),
             output_state: OutputState::new(&globals, &qh),
-            _compositor_state: compositor_state,
+            monitors: monitors.clone(),
+            compositor_state,
             _xdg_shell_state: shell,
             event_loop: Some(event_loop),
             handler: None,
             idle_actions,
             _idle_sender: idle_sender.clone(),
             windows: HashMap::new(),
+            context_menu: None,
             wayland_queue: qh.clone(),
             _loop_sender: loop_sender.clone(),
             loop_signal: loop_signal.clone(),
@@ -132,18 +224,38 @@ impl Application {
             seats: SeatState::new(&globals, &qh),
             xkb_context: Context::new(),
             text_input: text_input_global,
+            tablet_manager: tablet_manager_global,
+            data_device_manager: data_device_manager_global,
+            primary_selection_manager: primary_selection_manager_global,
+            clipboard: clipboard.clone(),
+            primary_clipboard: primary_clipboard.clone(),
+            shm,
+            cursor_shape_manager: cursor_shape_manager_global,
+            cursor_themes,
             loop_handle: loop_handle.clone(),
         };
         state.initial_seats();
+        // Get the initial state of the outputs, so that monitors are available before `run`
+        event_queue.roundtrip(&mut state)?;
+        WaylandSource::new(conn.clone(), event_queue)
+            .insert(loop_handle.clone())
+            .unwrap();
         Ok(Application {
             state: Arc::new(RwLock::new(Some(state))),
             compositor,
+            shm: wl_shm,
+            subcompositor,
+            fractional_scale_manager,
+            viewporter,
             wayland_queue: qh,
             loop_signal,
             idle_sender,
             loop_sender,
             loop_handle,
             xdg_shell: shell_ref,
+            clipboard,
+            primary_clipboard,
+            monitors,
             raw_display_handle: conn.backend().display_ptr().cast(),
         })
     }
@@ -184,8 +296,14 @@ impl Application {
     }
 
     pub fn clipboard(&self) -> clipboard::Clipboard {
-        // TODO: Wayland's clipboard is inherently asynchronous (as is the web)
-        clipboard::Clipboard {}
+        self.clipboard.clone()
+    }
+
+    /// The primary selection, which is pasted with the middle mouse button.
+    ///
+    /// If the compositor doesn't support this, the returned clipboard is always empty.
+    pub fn primary_clipboard(&self) -> clipboard::Clipboard {
+        self.primary_clipboard.clone()
     }
 
     pub fn get_locale() -> String {
@@ -14,46 +14,577 @@
 
 //! Interactions with the system pasteboard on wayland compositors.
 
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::reexports::{
+    calloop::channel,
+    client::{
+        event_created_child,
+        protocol::{
+            wl_data_device::{self, WlDataDevice},
+            wl_data_device_manager::WlDataDeviceManager,
+            wl_data_offer::{self, WlDataOffer},
+            wl_data_source::{self, WlDataSource},
+        },
+        Connection, Dispatch, Proxy, QueueHandle,
+    },
+    protocols::wp::primary_selection::zv1::client::{
+        zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1,
+        zwp_primary_selection_device_v1::{self, ZwpPrimarySelectionDeviceV1},
+        zwp_primary_selection_offer_v1::{self, ZwpPrimarySelectionOfferV1},
+        zwp_primary_selection_source_v1::{self, ZwpPrimarySelectionSourceV1},
+    },
+};
+use tracing::{debug, error, warn};
+
+use super::{ActiveAction, WaylandState};
 use crate::{ClipboardFormat, FormatId};
 
+const STRING_TARGETS: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "TEXT",
+    "STRING",
+];
+
+/// How long we wait for the selection owner to send us its data
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// The system clipboard.
-#[derive(Debug, Clone)]
-pub struct Clipboard {}
+#[derive(Clone)]
+pub struct Clipboard(Arc<RwLock<ClipboardState>>);
+
+/// Which of the selections a [`Clipboard`] accesses
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum Selection {
+    /// The regular clipboard, using `wl_data_device`
+    Clipboard,
+    /// The primary selection (used for middle click pasting), using `zwp_primary_selection_device_v1`
+    Primary,
+}
+
+struct ClipboardState {
+    selection: Selection,
+    /// Whether the compositor supports the protocol needed for `selection`. If not,
+    /// this clipboard is always empty
+    supported: bool,
+    connection: Connection,
+    loop_sender: channel::Sender<ActiveAction>,
+    /// The data we are offering, if we are the selection owner
+    contents: Option<Vec<ClipboardFormat>>,
+    /// The source through which we are offering `contents`
+    source: Option<SelectionSource>,
+    /// The current selection, as offered to us by the compositor
+    offer: Option<SelectionOffer>,
+}
+
+/// The offer of a selection by another client, from either protocol
+#[derive(Clone)]
+enum SelectionOffer {
+    Clipboard(WlDataOffer),
+    Primary(ZwpPrimarySelectionOfferV1),
+}
+
+/// Our offer of a selection to other clients, for either protocol
+#[derive(PartialEq)]
+pub(super) enum SelectionSource {
+    Clipboard(WlDataSource),
+    Primary(ZwpPrimarySelectionSourceV1),
+}
+
+impl std::fmt::Debug for Clipboard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.0.read().unwrap();
+        f.debug_struct("Clipboard")
+            .field("selection", &state.selection)
+            .field("supported", &state.supported)
+            .finish_non_exhaustive()
+    }
+}
 
 impl Clipboard {
+    pub(super) fn new(
+        selection: Selection,
+        supported: bool,
+        connection: Connection,
+        loop_sender: channel::Sender<ActiveAction>,
+    ) -> Self {
+        if !supported {
+            warn!(
+                "the compositor doesn't support the {:?} selection, so it will always be empty",
+                selection
+            );
+        }
+        Self(Arc::new(RwLock::new(ClipboardState {
+            selection,
+            supported,
+            connection,
+            loop_sender,
+            contents: None,
+            source: None,
+            offer: None,
+        })))
+    }
+
     /// Put a string onto the system clipboard.
-    pub fn put_string(&mut self, _str: impl AsRef<str>) {
-        todo!();
+    pub fn put_string(&mut self, s: impl AsRef<str>) {
+        let bytes = s.as_ref().as_bytes();
+        let formats = STRING_TARGETS
+            .iter()
+            .map(|format| ClipboardFormat::new(format, bytes))
+            .collect::<Vec<_>>();
+        self.put_formats(&formats);
     }
 
     /// Put multi-format data on the system clipboard.
-    pub fn put_formats(&mut self, _formats: &[ClipboardFormat]) {
-        tracing::warn!("clipboard copy not implemented");
-        todo!();
+    pub fn put_formats(&mut self, formats: &[ClipboardFormat]) {
+        let mut state = self.0.write().unwrap();
+        if !state.supported {
+            debug!(
+                "Clipboard::put_formats: the {:?} selection is not supported",
+                state.selection
+            );
+            return;
+        }
+        // We remember the contents straight away, so that reading back our own
+        // selection works even before the compositor has been told about it
+        state.contents = Some(formats.to_vec());
+        // Creating the source needs the seats, which are owned by the event loop
+        let clipboard = self.clone();
+        let result = state
+            .loop_sender
+            .send(ActiveAction::Callback(Box::new(move |state| {
+                state.offer_selection(&clipboard);
+            })));
+        if result.is_err() {
+            error!("Clipboard::put_formats: the event loop has already finished");
+        }
     }
 
     /// Get a string from the system clipboard, if one is available.
     pub fn get_string(&self) -> Option<String> {
-        todo!();
+        STRING_TARGETS.iter().find_map(|target| {
+            self.get_format(target)
+                .and_then(|data| String::from_utf8(data).ok())
+        })
     }
 
     /// Given a list of supported clipboard types, returns the supported type which has
     /// highest priority on the system clipboard, or `None` if no types are supported.
-    pub fn preferred_format(&self, _formats: &[FormatId]) -> Option<FormatId> {
-        tracing::warn!("clipboard preferred_format not implemented");
-        None
+    pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
+        let available = self.available_type_names();
+        formats
+            .iter()
+            .find(|f1| available.iter().any(|f2| *f1 == f2))
+            .copied()
     }
 
     /// Return data in a given format, if available.
     ///
     /// It is recommended that the `fmt` argument be a format returned by
     /// [`Clipboard::preferred_format`]
-    pub fn get_format(&self, _format: FormatId) -> Option<Vec<u8>> {
-        todo!();
+    pub fn get_format(&self, format: FormatId) -> Option<Vec<u8>> {
+        let state = self.0.read().unwrap();
+        if let Some(contents) = state.contents.as_ref() {
+            // We are the selection owner and can directly return the result
+            return contents
+                .iter()
+                .find(|it| it.identifier == format)
+                .map(|it| it.data.clone());
+        }
+        let offer = state.offer.clone()?;
+        if !offer.mime_types().iter().any(|it| it == format) {
+            return None;
+        }
+        let connection = state.connection.clone();
+        // This runs on the event loop's thread, so it blocks the UI until the owner has sent
+        // the data or `TRANSFER_TIMEOUT` passes, as the X11 backend does. The state isn't
+        // needed whilst we wait.
+        drop(state);
+        match receive(&connection, &offer, format) {
+            Ok(data) => Some(data),
+            Err(err) => {
+                warn!("Error in Clipboard::get_format: {:?}", err);
+                None
+            }
+        }
     }
 
     pub fn available_type_names(&self) -> Vec<String> {
-        tracing::warn!("clipboard available_type_names not implemented");
-        Vec::new()
+        let state = self.0.read().unwrap();
+        if let Some(contents) = state.contents.as_ref() {
+            return contents
+                .iter()
+                .map(|it| it.identifier.to_string())
+                .collect();
+        }
+        state
+            .offer
+            .as_ref()
+            .map(SelectionOffer::mime_types)
+            .unwrap_or_default()
+    }
+}
+
+/// Read the data in the given format from the selection owner.
+///
+/// This blocks until the owner has sent all the data, or until [`TRANSFER_TIMEOUT`].
+fn receive(
+    connection: &Connection,
+    offer: &SelectionOffer,
+    mime_type: &str,
+) -> std::io::Result<Vec<u8>> {
+    use nix::poll::{poll, PollFd, PollFlags};
+    use std::os::raw::c_int;
+
+    debug!("Getting clipboard contents in format {}", mime_type);
+    let (read, write) = nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC)?;
+    // Safety: We have just created these file descriptors, and nothing else owns them
+    let (mut read, write) = unsafe { (File::from_raw_fd(read), OwnedFd::from_raw_fd(write)) };
+    offer.receive(mime_type.to_string(), write.as_fd());
+    // Our copy of the write end must be closed, or we would never see the end of the data
+    drop(write);
+    connection.flush().map_err(std::io::Error::other)?;
+
+    let deadline = Instant::now() + TRANSFER_TIMEOUT;
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let now = Instant::now();
+        if deadline <= now {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Timeout while waiting for selection owner to send data",
+            ));
+        }
+        let mut poll_fds = [PollFd::new(read.as_raw_fd(), PollFlags::POLLIN)];
+        let poll_timeout = c_int::try_from(deadline.duration_since(now).as_millis())
+            .unwrap_or(c_int::MAX - 1)
+            .saturating_add(1);
+        match poll(&mut poll_fds, poll_timeout) {
+            Ok(0) => continue,
+            Ok(_) => {}
+            Err(nix::errno::Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+        match read.read(&mut buf) {
+            Ok(0) => return Ok(data),
+            Ok(n) => data.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl ClipboardState {
+    /// Find the data to send for a request for `mime_type` on our source
+    fn data_for(&self, mime_type: &str) -> Option<Vec<u8>> {
+        let contents = self.contents.as_ref()?;
+        contents
+            .iter()
+            .find(|it| it.identifier == mime_type)
+            .or_else(|| {
+                // We offer all of our string formats under each of the common names
+                if !STRING_TARGETS.contains(&mime_type) {
+                    return None;
+                }
+                contents
+                    .iter()
+                    .find(|it| STRING_TARGETS.contains(&it.identifier))
+            })
+            .map(|it| it.data.clone())
+    }
+
+    /// The selection owner has changed, and is now offering `offer` (if any)
+    fn set_offer(&mut self, offer: Option<SelectionOffer>) {
+        if let Some(old) = std::mem::replace(&mut self.offer, offer) {
+            old.destroy();
+        }
+    }
+
+    /// Another client asked for our selection in the format `mime_type`
+    fn send(&self, mime_type: String, fd: OwnedFd) {
+        let Some(data) = self.data_for(&mime_type) else {
+            warn!("Clipboard: asked to send unknown format {}", mime_type);
+            return;
+        };
+        // The receiver might be slow to read large amounts of data (or might even be
+        // ourselves), so we must not block the event loop on the transfer
+        std::thread::spawn(move || {
+            if let Err(err) = File::from(fd).write_all(&data) {
+                warn!("Clipboard: error sending {}: {:?}", mime_type, err);
+            }
+        });
+    }
+
+    /// `source` will no longer be used, generally because another client has taken the selection
+    fn cancelled(&mut self, source: SelectionSource) {
+        if self.source.as_ref() == Some(&source) {
+            debug!("Clipboard: lost {:?} selection ownership", self.selection);
+            self.source = None;
+            self.contents = None;
+        }
+        source.destroy();
+    }
+}
+
+impl SelectionOffer {
+    /// The mime types offered by the selection owner
+    fn mime_types(&self) -> Vec<String> {
+        let data = match self {
+            SelectionOffer::Clipboard(offer) => offer.data::<OfferData>(),
+            SelectionOffer::Primary(offer) => offer.data::<OfferData>(),
+        };
+        data.map(|data| data.0.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    fn receive(&self, mime_type: String, fd: BorrowedFd) {
+        match self {
+            SelectionOffer::Clipboard(offer) => offer.receive(mime_type, fd),
+            SelectionOffer::Primary(offer) => offer.receive(mime_type, fd),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            SelectionOffer::Clipboard(offer) => offer.destroy(),
+            SelectionOffer::Primary(offer) => offer.destroy(),
+        }
+    }
+}
+
+impl SelectionSource {
+    fn offer(&self, mime_type: String) {
+        match self {
+            SelectionSource::Clipboard(source) => source.offer(mime_type),
+            SelectionSource::Primary(source) => source.offer(mime_type),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            SelectionSource::Clipboard(source) => source.destroy(),
+            SelectionSource::Primary(source) => source.destroy(),
+        }
+    }
+}
+
+pub(super) struct DataDeviceManagerData;
+
+pub(super) struct DataDeviceData;
+
+pub(super) struct PrimarySelectionManagerData;
+
+pub(super) struct PrimarySelectionDeviceData;
+
+/// The mime types of an offer, which are sent before the offer is used
+#[derive(Default)]
+struct OfferData(Mutex<Vec<String>>);
+
+struct DataSourceData;
+
+impl WaylandState {
+    /// Make the contents of `clipboard` the selection of each of our seats
+    fn offer_selection(&mut self, clipboard: &Clipboard) {
+        let mut state = clipboard.0.write().unwrap();
+        let Some(contents) = state.contents.as_ref() else {
+            return;
+        };
+        let source = match state.selection {
+            Selection::Clipboard => {
+                let Some(manager) = self.data_device_manager.as_ref() else {
+                    return;
+                };
+                SelectionSource::Clipboard(
+                    manager.create_data_source(&self.wayland_queue, DataSourceData),
+                )
+            }
+            Selection::Primary => {
+                let Some(manager) = self.primary_selection_manager.as_ref() else {
+                    return;
+                };
+                SelectionSource::Primary(manager.create_source(&self.wayland_queue, DataSourceData))
+            }
+        };
+        let mut offered: Vec<&str> = Vec::new();
+        for format in contents {
+            // Strings are offered under every common name, as not all clients agree on one
+            let aliases = if STRING_TARGETS.contains(&format.identifier) {
+                &STRING_TARGETS[..]
+            } else {
+                std::slice::from_ref(&format.identifier)
+            };
+            for &mime_type in aliases {
+                if !offered.contains(&mime_type) {
+                    source.offer(mime_type.to_string());
+                    offered.push(mime_type);
+                }
+            }
+        }
+        for seat in &self.input_states {
+            seat.set_selection(&source);
+        }
+        if let Some(old) = state.source.replace(source) {
+            old.destroy();
+        }
+    }
+}
+
+impl Dispatch<WlDataDeviceManager, DataDeviceManagerData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &WlDataDeviceManager,
+        event: <WlDataDeviceManager as Proxy>::Event,
+        _: &DataDeviceManagerData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        tracing::error!(?event, "unexpected wl_data_device_manager event");
+    }
+}
+
+impl Dispatch<WlDataDevice, DataDeviceData> for WaylandState {
+    event_created_child!(WaylandState, WlDataDevice, [
+        wl_data_device::EVT_DATA_OFFER_OPCODE => (WlDataOffer, OfferData::default()),
+    ]);
+
+    fn event(
+        state: &mut Self,
+        _: &WlDataDevice,
+        event: <WlDataDevice as Proxy>::Event,
+        _: &DataDeviceData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_data_device::Event::Selection { id } => {
+                let mut clipboard = state.clipboard.0.write().unwrap();
+                clipboard.set_offer(id.map(SelectionOffer::Clipboard));
+            }
+            // We don't support drag and drop, so decline any offers
+            wl_data_device::Event::Enter { id: Some(id), .. } => id.destroy(),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlDataOffer, OfferData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &WlDataOffer,
+        event: <WlDataOffer as Proxy>::Event,
+        data: &OfferData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_data_offer::Event::Offer { mime_type } = event {
+            data.0.lock().unwrap().push(mime_type);
+        }
+    }
+}
+
+impl Dispatch<WlDataSource, DataSourceData> for WaylandState {
+    fn event(
+        state: &mut Self,
+        proxy: &WlDataSource,
+        event: <WlDataSource as Proxy>::Event,
+        _: &DataSourceData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_data_source::Event::Send { mime_type, fd } => {
+                state.clipboard.0.read().unwrap().send(mime_type, fd);
+            }
+            wl_data_source::Event::Cancelled => {
+                let mut clipboard = state.clipboard.0.write().unwrap();
+                clipboard.cancelled(SelectionSource::Clipboard(proxy.clone()));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpPrimarySelectionDeviceManagerV1, PrimarySelectionManagerData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &ZwpPrimarySelectionDeviceManagerV1,
+        event: <ZwpPrimarySelectionDeviceManagerV1 as Proxy>::Event,
+        _: &PrimarySelectionManagerData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        tracing::error!(
+            ?event,
+            "unexpected zwp_primary_selection_device_manager_v1 event"
+        );
+    }
+}
+
+impl Dispatch<ZwpPrimarySelectionDeviceV1, PrimarySelectionDeviceData> for WaylandState {
+    event_created_child!(WaylandState, ZwpPrimarySelectionDeviceV1, [
+        zwp_primary_selection_device_v1::EVT_DATA_OFFER_OPCODE => (ZwpPrimarySelectionOfferV1, OfferData::default()),
+    ]);
+
+    fn event(
+        state: &mut Self,
+        _: &ZwpPrimarySelectionDeviceV1,
+        event: <ZwpPrimarySelectionDeviceV1 as Proxy>::Event,
+        _: &PrimarySelectionDeviceData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_primary_selection_device_v1::Event::Selection { id } = event {
+            let mut clipboard = state.primary_clipboard.0.write().unwrap();
+            clipboard.set_offer(id.map(SelectionOffer::Primary));
+        }
+    }
+}
+
+impl Dispatch<ZwpPrimarySelectionOfferV1, OfferData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &ZwpPrimarySelectionOfferV1,
+        event: <ZwpPrimarySelectionOfferV1 as Proxy>::Event,
+        data: &OfferData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_primary_selection_offer_v1::Event::Offer { mime_type } = event {
+            data.0.lock().unwrap().push(mime_type);
+        }
+    }
+}
+
+impl Dispatch<ZwpPrimarySelectionSourceV1, DataSourceData> for WaylandState {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwpPrimarySelectionSourceV1,
+        event: <ZwpPrimarySelectionSourceV1 as Proxy>::Event,
+        _: &DataSourceData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_primary_selection_source_v1::Event::Send { mime_type, fd } => {
+                state
+                    .primary_clipboard
+                    .0
+                    .read()
+                    .unwrap()
+                    .send(mime_type, fd);
+            }
+            zwp_primary_selection_source_v1::Event::Cancelled => {
+                let mut clipboard = state.primary_clipboard.0.write().unwrap();
+                clipboard.cancelled(SelectionSource::Primary(proxy.clone()));
+            }
+            _ => {}
+        }
     }
 }
@@ -0,0 +1,324 @@
+// Copyright 2023 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mouse cursors on wayland.
+//!
+//! Where the compositor supports `wp_cursor_shape_v1`, we just ask it to draw the cursor.
+//! Otherwise, we draw the cursor ourselves from the user's xcursor theme.
+//! Custom cursors are always drawn by us.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use smithay_client_toolkit::{
+    reexports::{
+        client::{
+            protocol::{
+                wl_buffer::WlBuffer,
+                wl_pointer::WlPointer,
+                wl_shm::{self, WlShm},
+                wl_surface::WlSurface,
+            },
+            Connection, Dispatch, Proxy, QueueHandle,
+        },
+        protocols::wp::cursor_shape::v1::client::{
+            wp_cursor_shape_device_v1::{Shape, WpCursorShapeDeviceV1},
+            wp_cursor_shape_manager_v1::WpCursorShapeManagerV1,
+        },
+    },
+    seat::pointer::CursorIcon,
+    shm::{raw::RawPool, Shm},
+};
+use wayland_cursor::{CursorImageBuffer, CursorTheme};
+
+use super::WaylandState;
+use crate::mouse::{Cursor, CursorDesc};
+
+/// The cursor size used if `XCURSOR_SIZE` isn't set, which matches the other toolkits
+const DEFAULT_CURSOR_SIZE: u32 = 24;
+
+pub(super) struct CursorShapeManagerData;
+
+/// The user data of the surfaces we draw themed cursors on
+pub(super) struct CursorSurfaceData;
+
+/// A cursor image created by [`make_cursor`](crate::WindowHandle::make_cursor).
+///
+/// The image is uploaded to the compositor once, and shared between every window and seat.
+#[derive(Clone)]
+pub struct CustomCursor(Arc<CustomCursorData>);
+
+struct CustomCursorData {
+    buffer: WlBuffer,
+    width: i32,
+    height: i32,
+    hot_x: i32,
+    hot_y: i32,
+}
+
+/// The user data of the buffers of custom cursors
+struct CustomCursorBufferData;
+
+impl CustomCursor {
+    pub(super) fn new(
+        shm: &WlShm,
+        qh: &QueueHandle<WaylandState>,
+        desc: &CursorDesc,
+    ) -> Option<CustomCursor> {
+        let image = &desc.image;
+        if image.width() == 0 || image.height() == 0 {
+            tracing::warn!("cannot create a custom cursor from an empty image");
+            return None;
+        }
+        let (width, height) = (image.width() as i32, image.height() as i32);
+        let pixels = image.to_premultiplied_argb32();
+        let len = pixels.len() * 4;
+        let mut pool = match RawPool::new(len, &Shm::from(shm.clone())) {
+            Ok(pool) => pool,
+            Err(err) => {
+                tracing::error!("failed to create memory pool for custom cursor: {err}");
+                return None;
+            }
+        };
+        argb32_to_shm(&pixels, pool.mmap());
+        let buffer = pool.create_buffer(
+            0,
+            width,
+            height,
+            width * 4,
+            wl_shm::Format::Argb8888,
+            CustomCursorBufferData,
+            qh,
+        );
+        // The buffer keeps the memory alive, so we don't need the pool any more
+        Some(CustomCursor(Arc::new(CustomCursorData {
+            buffer,
+            width,
+            height,
+            hot_x: desc.hot.x.round() as i32,
+            hot_y: desc.hot.y.round() as i32,
+        })))
+    }
+
+    /// Show this cursor on `surface`, and make that the cursor of `pointer`, which is over a
+    /// window with the integer `scale`
+    pub(super) fn apply(&self, pointer: &WlPointer, serial: u32, surface: &WlSurface, scale: u32) {
+        let data = &self.0;
+        // The image is in physical pixels, as on the other platforms
+        let scale = buffer_scale((data.width as u32, data.height as u32), scale) as i32;
+        surface.set_buffer_scale(scale);
+        surface.attach(Some(&data.buffer), 0, 0);
+        surface.damage(0, 0, data.width / scale, data.height / scale);
+        surface.commit();
+        pointer.set_cursor(
+            serial,
+            Some(surface),
+            data.hot_x / scale,
+            data.hot_y / scale,
+        );
+    }
+}
+
+impl PartialEq for CustomCursor {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CustomCursor {}
+
+impl Drop for CustomCursorData {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+    }
+}
+
+/// The buffer scale to show a cursor image with the size `(width, height)` in pixels at, over
+/// a window with the integer `scale`.
+///
+/// The size of a buffer must be a multiple of its scale, so images which aren't are shown at a
+/// scale of 1, which the compositor stretches instead.
+pub(super) fn buffer_scale((width, height): (u32, u32), scale: u32) -> u32 {
+    if scale > 1 && width % scale == 0 && height % scale == 0 {
+        scale
+    } else {
+        1
+    }
+}
+
+/// The user's xcursor theme, loaded at each scale we have needed it at
+pub(super) struct CursorThemes {
+    connection: Connection,
+    shm: WlShm,
+    name: String,
+    /// The size of cursors at a scale of 1
+    size: u32,
+    themes: HashMap<u32, CursorTheme>,
+}
+
+impl CursorThemes {
+    pub(super) fn new(connection: Connection, shm: WlShm) -> Self {
+        // These are the same environment variables that `libwayland-cursor` uses
+        let name = std::env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".into());
+        let size = std::env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|it| it.parse().ok())
+            .unwrap_or(DEFAULT_CURSOR_SIZE);
+        CursorThemes {
+            connection,
+            shm,
+            name,
+            size,
+            themes: HashMap::new(),
+        }
+    }
+
+    /// Get the first frame of the image for the first of `names` which the theme has, for a
+    /// surface with the given buffer scale
+    pub(super) fn image(&mut self, names: &[&str], scale: u32) -> Option<&CursorImageBuffer> {
+        let theme = match self.themes.entry(scale) {
+            std::collections::hash_map::Entry::Occupied(it) => it.into_mut(),
+            std::collections::hash_map::Entry::Vacant(it) => {
+                let theme = CursorTheme::load_from_name(
+                    &self.connection,
+                    self.shm.clone(),
+                    &self.name,
+                    self.size * scale,
+                );
+                match theme {
+                    Ok(theme) => it.insert(theme),
+                    Err(err) => {
+                        tracing::error!("failed to load cursor theme {}: {err}", self.name);
+                        return None;
+                    }
+                }
+            }
+        };
+        // Some themes only include the legacy X11 names, so we need to find the first
+        // name which the theme has. We can't return from within the loop, as that
+        // would keep `theme` borrowed for the next iteration
+        let name = names.iter().find(|name| theme.get_cursor(name).is_some())?;
+        Some(&theme.get_cursor(name)?[0])
+    }
+}
+
+/// Write premultiplied ARGB32 `pixels` into `dst`, a buffer in the `Argb8888` format.
+///
+/// The pixels of `wl_shm` formats are little-endian, whatever the endianness of the machine.
+pub(super) fn argb32_to_shm(pixels: &[u32], dst: &mut [u8]) {
+    for (dst, pixel) in dst.chunks_exact_mut(4).zip(pixels) {
+        dst.copy_from_slice(&pixel.to_le_bytes());
+    }
+}
+
+/// The shape of `cursor` for `wp_cursor_shape_device_v1`
+pub(super) fn cursor_shape(cursor: &Cursor) -> Shape {
+    #[allow(deprecated)]
+    match cursor {
+        Cursor::Arrow => Shape::Default,
+        Cursor::IBeam => Shape::Text,
+        Cursor::Pointer => Shape::Pointer,
+        Cursor::Crosshair => Shape::Crosshair,
+        Cursor::OpenHand => Shape::Grab,
+        Cursor::NotAllowed => Shape::NotAllowed,
+        Cursor::ResizeLeftRight => Shape::ColResize,
+        Cursor::ResizeUpDown => Shape::RowResize,
+        Cursor::Custom(_) => Shape::Default,
+    }
+}
+
+/// The shape of a cursor shown over our client side decorations
+pub(super) fn icon_shape(icon: CursorIcon) -> Shape {
+    match icon {
+        CursorIcon::NResize => Shape::NResize,
+        CursorIcon::NeResize => Shape::NeResize,
+        CursorIcon::EResize => Shape::EResize,
+        CursorIcon::SeResize => Shape::SeResize,
+        CursorIcon::SResize => Shape::SResize,
+        CursorIcon::SwResize => Shape::SwResize,
+        CursorIcon::WResize => Shape::WResize,
+        CursorIcon::NwResize => Shape::NwResize,
+        // The decorations don't use any other cursors
+        _ => Shape::Default,
+    }
+}
+
+/// The names which `cursor` could have in an xcursor theme, in order of preference.
+///
+/// The first name is the name from the CSS specification, which is used by newer themes.
+pub(super) fn cursor_names(cursor: &Cursor) -> &'static [&'static str] {
+    #[allow(deprecated)]
+    match cursor {
+        Cursor::Arrow | Cursor::Custom(_) => &["default", "left_ptr"],
+        Cursor::IBeam => &["text", "xterm"],
+        Cursor::Pointer => &["pointer", "hand2", "hand1"],
+        Cursor::Crosshair => &["crosshair", "cross"],
+        Cursor::OpenHand => &["grab", "openhand", "hand1"],
+        Cursor::NotAllowed => &["not-allowed", "crossed_circle"],
+        Cursor::ResizeLeftRight => &["col-resize", "sb_h_double_arrow"],
+        Cursor::ResizeUpDown => &["row-resize", "sb_v_double_arrow"],
+    }
+}
+
+impl Dispatch<WpCursorShapeManagerV1, CursorShapeManagerData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &WpCursorShapeManagerV1,
+        event: <WpCursorShapeManagerV1 as Proxy>::Event,
+        _: &CursorShapeManagerData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        tracing::error!(?event, "unexpected wp_cursor_shape_manager_v1 event");
+    }
+}
+
+impl Dispatch<WpCursorShapeDeviceV1, CursorShapeManagerData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &WpCursorShapeDeviceV1,
+        event: <WpCursorShapeDeviceV1 as Proxy>::Event,
+        _: &CursorShapeManagerData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        tracing::error!(?event, "unexpected wp_cursor_shape_device_v1 event");
+    }
+}
+
+impl Dispatch<WlBuffer, CustomCursorBufferData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &WlBuffer,
+        _: <WlBuffer as Proxy>::Event,
+        _: &CustomCursorBufferData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // The buffer is never modified, so we don't care when it is released
+    }
+}
+
+impl Dispatch<WlSurface, CursorSurfaceData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &WlSurface,
+        _: <WlSurface as Proxy>::Event,
+        _: &CursorSurfaceData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // We don't care which outputs the cursor is on, as we use the scale of the window
+    }
+}
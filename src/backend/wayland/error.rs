@@ -18,7 +18,7 @@ use std::fmt;
 
 use smithay_client_toolkit::reexports::{
     calloop,
-    client::{globals::BindError, ConnectError},
+    client::{globals::BindError, ConnectError, DispatchError},
 };
 
 #[derive(Debug)]
@@ -26,6 +26,7 @@ pub enum Error {
     Connect(ConnectError),
     Bind(BindError),
     Calloop(calloop::Error),
+    Dispatch(DispatchError),
 }
 
 impl fmt::Display for Error {
@@ -34,6 +35,7 @@ impl fmt::Display for Error {
             Error::Connect(e) => write!(f, "could not connect to the wayland server: {e:}"),
             Error::Bind(e) => write!(f, "could not bind a wayland global: {e:}"),
             Error::Calloop(e) => write!(f, "calloop failed: {e:}"),
+            Error::Dispatch(e) => write!(f, "could not dispatch wayland events: {e:}"),
         }
     }
 }
@@ -57,3 +59,9 @@ impl From<calloop::Error> for Error {
         Self::Calloop(value)
     }
 }
+
+impl From<DispatchError> for Error {
+    fn from(value: DispatchError) -> Self {
+        Self::Dispatch(value)
+    }
+}
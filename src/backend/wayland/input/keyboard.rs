@@ -156,16 +156,25 @@ impl Dispatch<wl_keyboard::WlKeyboard, KeyboardUserData> for WaylandState {
                 }
             },
             wl_keyboard::Event::Enter {
-                serial: _,
+                serial,
                 surface,
                 // TODO: How should we handle `keys`?
                 keys: _,
             } => {
                 let seat = input_state(&mut state.input_states, data.0);
-                seat.window_focus_enter(&mut state.windows, WindowId::of_surface(&surface));
+                seat.latest_serial = Some(serial);
+                // The context menu takes the keyboard focus whilst it is open
+                seat.menu_focused = state
+                    .context_menu
+                    .as_ref()
+                    .is_some_and(|menu| menu.level_of(&surface).is_some());
+                if !seat.menu_focused {
+                    seat.window_focus_enter(&mut state.windows, WindowId::of_surface(&surface));
+                }
             }
             wl_keyboard::Event::Leave { .. } => {
                 let seat = input_state(&mut state.input_states, data.0);
+                seat.menu_focused = false;
                 seat.window_focus_leave(&mut state.windows);
                 if let Some(keyboard_state) = seat.keyboard_state.as_mut() {
                     if let Some((token, _)) = keyboard_state.repeat_details.take() {
@@ -196,7 +205,7 @@ impl Dispatch<wl_keyboard::WlKeyboard, KeyboardUserData> for WaylandState {
                 })
             }
             wl_keyboard::Event::Key {
-                serial: _,
+                serial,
                 time: _, // TODO: Report the time of the event to the keyboard
                 key,
                 state: key_state,
@@ -206,6 +215,7 @@ impl Dispatch<wl_keyboard::WlKeyboard, KeyboardUserData> for WaylandState {
                 let scancode = key + 8;
 
                 let seat = input_state(&mut state.input_states, data.0);
+                seat.latest_serial = Some(serial);
 
                 let key_state = match key_state {
                     WEnum::Value(wl_keyboard::KeyState::Pressed) => KeyState::Down,
@@ -214,6 +224,23 @@ impl Dispatch<wl_keyboard::WlKeyboard, KeyboardUserData> for WaylandState {
                     WEnum::Unknown(_) => unreachable!(),
                 };
 
+                if seat.menu_focused {
+                    // Menus don't repeat keys, or care about them being released
+                    if key_state != KeyState::Down {
+                        return;
+                    }
+                    let Some((xkb_state, _)) = seat
+                        .keyboard_state
+                        .as_mut()
+                        .and_then(|keyboard| keyboard.xkb_state.as_mut())
+                    else {
+                        return;
+                    };
+                    let keysym = xkb_state.get_one_sym(scancode);
+                    let event = xkb_state.key_event(scancode, keysym, key_state, false);
+                    state.context_menu_key_down(&event.key);
+                    return;
+                }
                 seat.handle_key_event(scancode, key_state, false, &mut state.windows);
                 let keyboard_info = seat.keyboard_state.as_mut().unwrap();
                 match keyboard_info.repeat_settings {
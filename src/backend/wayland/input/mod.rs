@@ -1,28 +1,48 @@
-use self::{keyboard::KeyboardState, text_input::InputState};
+use self::{
+    keyboard::KeyboardState, pointer::PointerState, tablet::TabletSeatState,
+    text_input::InputState, touch::TouchState,
+};
 use super::{
+    clipboard::{DataDeviceData, PrimarySelectionDeviceData, SelectionSource},
     window::{WaylandWindowState, WindowId},
     WaylandState,
 };
 use crate::{
     backend::shared::xkb::{xkb_simulate_input, KeyboardHandled},
     text::InputHandler,
-    Counter, TextFieldToken, WinHandler,
+    Counter, Modifiers, TextFieldToken, WinHandler,
 };
 use flo_binding::{Binding, Bound, MutableBound};
 use keyboard_types::KeyState;
 use smithay_client_toolkit::{
     delegate_seat,
     reexports::{
-        client::{protocol::wl_seat, Connection, QueueHandle},
-        protocols::wp::text_input::zv3::client::zwp_text_input_v3,
+        client::{
+            protocol::{
+                wl_data_device::{self, WlDataDevice},
+                wl_seat,
+            },
+            Connection, Proxy, QueueHandle,
+        },
+        protocols::{
+            wp::{
+                primary_selection::zv1::client::zwp_primary_selection_device_v1::ZwpPrimarySelectionDeviceV1,
+                text_input::zv3::client::zwp_text_input_v3,
+            },
+            xdg::shell::client::xdg_popup::XdgPopup,
+        },
     },
     seat::SeatHandler,
 };
 use std::collections::HashMap;
 
 mod keyboard;
+mod pointer;
+mod tablet;
 mod text_input;
+mod touch;
 
+pub(super) use tablet::TabletManagerData;
 pub(super) use text_input::TextInputManagerData;
 
 #[derive(Debug)]
@@ -71,8 +91,17 @@ pub(super) struct SeatInfo {
     id: SeatName,
     seat: wl_seat::WlSeat,
     keyboard_state: Option<KeyboardState>,
+    pointer_state: Option<PointerState>,
+    touch_state: Option<TouchState>,
+    tablet_state: Option<TabletSeatState>,
     input_state: Option<InputState>,
+    data_device: Option<WlDataDevice>,
+    primary_selection_device: Option<ZwpPrimarySelectionDeviceV1>,
     keyboard_focused: Option<WindowId>,
+    /// Whether the keyboard is focused on the context menu, rather than a window
+    menu_focused: bool,
+    /// The serial of the latest input event on this seat, which is needed to set the selection
+    latest_serial: Option<u32>,
 
     text_field_owner: TextFieldOwner,
 }
@@ -353,6 +382,34 @@ impl SeatInfo {
         }
     }
 
+    /// Make `source` the selection of this seat, if we have had any input events on it
+    pub(super) fn set_selection(&self, source: &SelectionSource) {
+        let Some(serial) = self.latest_serial else {
+            return;
+        };
+        match source {
+            SelectionSource::Clipboard(source) => {
+                if let Some(device) = self.data_device.as_ref() {
+                    device.set_selection(Some(source), serial);
+                }
+            }
+            SelectionSource::Primary(source) => {
+                if let Some(device) = self.primary_selection_device.as_ref() {
+                    device.set_selection(Some(source), serial);
+                }
+            }
+        }
+    }
+
+    /// The keyboard modifiers currently active on this seat, used for pointer events
+    fn modifiers(&self) -> Modifiers {
+        self.keyboard_state
+            .as_ref()
+            .and_then(|keyboard| keyboard.xkb_state.as_ref())
+            .map(|(xkb_state, _)| xkb_state.active_modifiers())
+            .unwrap_or_default()
+    }
+
     pub fn handle_key_event(
         &mut self,
         scancode: u32,
@@ -508,6 +565,32 @@ impl WaylandState {
     // fn seat_ref(&self, name: SeatName) -> &SeatInfo;
 }
 
+impl WaylandState {
+    /// Give `popup` an explicit grab on the seat which most recently had input, as that
+    /// input is what opened the popup
+    pub(super) fn grab_popup(&self, popup: &XdgPopup) {
+        let Some((seat, serial)) = self
+            .input_states
+            .iter()
+            .filter_map(|seat| Some((seat, seat.latest_serial?)))
+            .max_by_key(|(_, serial)| *serial)
+        else {
+            tracing::warn!("cannot grab popup without any input events");
+            return;
+        };
+        popup.grab(&seat.seat, serial);
+    }
+
+    /// The seat with a pointer button held, and the serial of pressing that button, for
+    /// starting an interactive move or resize
+    pub(super) fn held_pointer_button(&self) -> Option<(&wl_seat::WlSeat, u32)> {
+        self.input_states
+            .iter()
+            .filter_map(|seat| Some((&seat.seat, seat.pointer_state.as_ref()?.held_serial()?)))
+            .max_by_key(|(_, serial)| *serial)
+    }
+}
+
 pub(super) fn input_state(seats: &mut [SeatInfo], name: SeatName) -> &mut SeatInfo {
     seats
         .iter_mut()
@@ -522,8 +605,15 @@ impl WaylandState {
             id,
             seat,
             keyboard_state: None,
+            pointer_state: None,
+            touch_state: None,
+            tablet_state: None,
             input_state: None,
+            data_device: None,
+            primary_selection_device: None,
             keyboard_focused: None,
+            menu_focused: false,
+            latest_serial: None,
             text_field_owner: TextFieldOwner::Neither,
         };
         let idx = self.input_states.len();
@@ -533,6 +623,15 @@ impl WaylandState {
             .text_input
             .as_ref()
             .map(|text_input| InputState::new(text_input, &input.seat, &self.wayland_queue, id));
+        input.tablet_state = self.tablet_manager.as_ref().map(|tablet_manager| {
+            TabletSeatState::new(tablet_manager, &input.seat, &self.wayland_queue, id)
+        });
+        input.data_device = self.data_device_manager.as_ref().map(|manager| {
+            manager.get_data_device(&input.seat, &self.wayland_queue, DataDeviceData)
+        });
+        input.primary_selection_device = self.primary_selection_manager.as_ref().map(|manager| {
+            manager.get_device(&input.seat, &self.wayland_queue, PrimarySelectionDeviceData)
+        });
     }
 
     pub(super) fn initial_seats(&mut self) {
@@ -558,6 +657,9 @@ impl SeatHandler for WaylandState {
         seat: wl_seat::WlSeat,
         capability: smithay_client_toolkit::seat::Capability,
     ) {
+        // Needed to set up the cursor of a new pointer
+        let compositor = self.compositor_state.wl_compositor().clone();
+        let cursor_shape_manager = self.cursor_shape_manager.clone();
         let seat_info = self.info_of_seat(&seat);
 
         match capability {
@@ -565,8 +667,20 @@ impl SeatHandler for WaylandState {
                 let state = KeyboardState::new(qh, seat_info.id, seat);
                 seat_info.keyboard_state = Some(state);
             }
-            smithay_client_toolkit::seat::Capability::Pointer => {}
-            smithay_client_toolkit::seat::Capability::Touch => {}
+            smithay_client_toolkit::seat::Capability::Pointer => {
+                let state = PointerState::new(
+                    qh,
+                    seat_info.id,
+                    seat,
+                    &compositor,
+                    cursor_shape_manager.as_ref(),
+                );
+                seat_info.pointer_state = Some(state);
+            }
+            smithay_client_toolkit::seat::Capability::Touch => {
+                let state = TouchState::new(qh, seat_info.id, seat);
+                seat_info.touch_state = Some(state);
+            }
             it => tracing::warn!(?seat, "Unknown seat capability {it}"),
         }
     }
@@ -581,13 +695,22 @@ impl SeatHandler for WaylandState {
         let state = self.info_of_seat(&seat);
         match capability {
             smithay_client_toolkit::seat::Capability::Keyboard => state.destroy_keyboard(),
-            smithay_client_toolkit::seat::Capability::Pointer => {}
-            smithay_client_toolkit::seat::Capability::Touch => {}
+            smithay_client_toolkit::seat::Capability::Pointer => state.pointer_state = None,
+            smithay_client_toolkit::seat::Capability::Touch => state.touch_state = None,
             it => tracing::info!(?seat, "Removed unknown seat capability {it}"),
         }
     }
 
     fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: wl_seat::WlSeat) {
+        let info = self.info_of_seat(&seat);
+        if let Some(device) = info.data_device.take() {
+            if device.version() >= wl_data_device::REQ_RELEASE_SINCE {
+                device.release();
+            }
+        }
+        if let Some(device) = info.primary_selection_device.take() {
+            device.destroy();
+        }
         // Keep every other seat
         self.input_states.retain(|it| it.seat != seat)
     }
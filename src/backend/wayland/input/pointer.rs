@@ -0,0 +1,532 @@
+use std::time::Duration;
+
+use smithay_client_toolkit::{
+    reexports::{
+        client::{
+            protocol::{wl_compositor::WlCompositor, wl_pointer, wl_seat, wl_surface::WlSurface},
+            Connection, Dispatch, Proxy, QueueHandle, WEnum,
+        },
+        csd_frame::FrameClick,
+        protocols::wp::cursor_shape::v1::client::{
+            wp_cursor_shape_device_v1::WpCursorShapeDeviceV1,
+            wp_cursor_shape_manager_v1::WpCursorShapeManagerV1,
+        },
+    },
+    seat::pointer::CursorIcon,
+};
+
+use crate::{
+    backend::wayland::{
+        cursor::{
+            buffer_scale, cursor_names, cursor_shape, icon_shape, CursorShapeManagerData,
+            CursorSurfaceData, CursorThemes,
+        },
+        menu::ContextMenu,
+        window::WindowId,
+    },
+    common_util::ClickCounter,
+    kurbo::{Point, Vec2},
+    pointer::{MouseInfo, PointerId, PointerType},
+    Cursor, Modifiers, PointerButton, PointerButtons, PointerEvent, WinHandler,
+};
+
+use super::{input_state, SeatName, WaylandState, Windows};
+
+// Linux input event codes for mouse buttons, from `linux/input-event-codes.h`
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const BTN_MIDDLE: u32 = 0x112;
+const BTN_SIDE: u32 = 0x113;
+const BTN_EXTRA: u32 = 0x114;
+const BTN_FORWARD: u32 = 0x115;
+const BTN_BACK: u32 = 0x116;
+
+/// The wheel delta reported for each discrete scroll step.
+///
+/// We use a delta of 120 per tick to match the behaviour of Windows (and our X11 backend).
+const WHEEL_DELTA_PER_STEP: f64 = 120.0;
+
+/// The seat identifier of this pointer
+struct PointerUserData(SeatName);
+
+/// The client side decorations of a window which the pointer is over
+struct FrameFocus {
+    window: WindowId,
+    /// The part of the decorations the pointer is over
+    surface: WlSurface,
+    /// The cursor which the decorations asked for
+    icon: CursorIcon,
+}
+
+/// The popup of the context menu which the pointer is over
+struct MenuFocus {
+    surface: WlSurface,
+    /// The integer scale the menu is drawn at, which the cursor should match
+    scale: u32,
+}
+
+pub(super) struct PointerState {
+    pointer: wl_pointer::WlPointer,
+    id: PointerId,
+    /// The window which the pointer is currently over, if any
+    focus: Option<WindowId>,
+    /// The window whose decorations the pointer is currently over, if any
+    frame_focus: Option<FrameFocus>,
+    /// The popup of the context menu the pointer is currently over, if any
+    menu_focus: Option<MenuFocus>,
+    /// The position of the pointer within `focus`, in display points
+    pos: Point,
+    buttons: PointerButtons,
+    click_counter: ClickCounter,
+    /// Continuous scroll amounts received since the last `frame` event
+    pending_scroll: Vec2,
+    /// Discrete scroll steps received since the last `frame` event
+    pending_discrete: Vec2,
+    /// The serial of the latest button press on our windows
+    press_serial: Option<u32>,
+    /// The serial of the latest `enter` event, which is needed to set the cursor
+    enter_serial: Option<u32>,
+    /// Used to set the cursor if the compositor supports `wp_cursor_shape_v1`
+    shape_device: Option<WpCursorShapeDeviceV1>,
+    /// The surface we draw the cursor on otherwise
+    cursor_surface: WlSurface,
+}
+
+impl PointerState {
+    pub(super) fn new(
+        qh: &QueueHandle<WaylandState>,
+        name: SeatName,
+        seat: wl_seat::WlSeat,
+        compositor: &WlCompositor,
+        cursor_shape_manager: Option<&WpCursorShapeManagerV1>,
+    ) -> Self {
+        let pointer = seat.get_pointer(qh, PointerUserData(name));
+        let shape_device = cursor_shape_manager
+            .map(|manager| manager.get_pointer(&pointer, qh, CursorShapeManagerData));
+        PointerState {
+            pointer,
+            id: PointerId(name.0),
+            focus: None,
+            frame_focus: None,
+            menu_focus: None,
+            pos: Point::ZERO,
+            buttons: PointerButtons::new(),
+            click_counter: ClickCounter::default(),
+            pending_scroll: Vec2::ZERO,
+            pending_discrete: Vec2::ZERO,
+            press_serial: None,
+            enter_serial: None,
+            shape_device,
+            cursor_surface: compositor.create_surface(qh, CursorSurfaceData),
+        }
+    }
+
+    /// The serial of pressing a button on our windows, if a button is still held
+    pub(super) fn held_serial(&self) -> Option<u32> {
+        if self.buttons.is_empty() {
+            return None;
+        }
+        self.press_serial
+    }
+
+    /// Show the cursor of the window the pointer is over
+    // The interior mutability of `WindowId` is the liveness flag of its `ObjectId`, which is
+    // only hashed and compared by address, so it can't change the key
+    #[allow(clippy::mutable_key_type)]
+    fn update_cursor(&self, windows: &Windows, themes: &mut CursorThemes) {
+        let Some(serial) = self.enter_serial else {
+            return;
+        };
+        if let Some(menu_focus) = self.menu_focus.as_ref() {
+            if let Some(shape_device) = self.shape_device.as_ref() {
+                shape_device.set_shape(serial, cursor_shape(&Cursor::Arrow));
+                return;
+            }
+            let names = cursor_names(&Cursor::Arrow);
+            self.show_themed_cursor(serial, themes, names, menu_focus.scale);
+            return;
+        }
+        if let Some(frame_focus) = self.frame_focus.as_ref() {
+            let Some(window) = windows.get(&frame_focus.window) else {
+                return;
+            };
+            let icon = frame_focus.icon;
+            if let Some(shape_device) = self.shape_device.as_ref() {
+                shape_device.set_shape(serial, icon_shape(icon));
+                return;
+            }
+            let names = std::iter::once(icon.name())
+                .chain(icon.alt_names().iter().copied())
+                .collect::<Vec<_>>();
+            self.show_themed_cursor(serial, themes, &names, window.buffer_scale());
+            return;
+        }
+        let Some(window) = self.focus.as_ref().and_then(|focus| windows.get(focus)) else {
+            return;
+        };
+        let cursor = window.cursor();
+        if let Cursor::Custom(custom) = cursor {
+            if let Some(custom) = custom.wayland() {
+                custom.apply(
+                    &self.pointer,
+                    serial,
+                    &self.cursor_surface,
+                    window.buffer_scale(),
+                );
+                return;
+            }
+        }
+        if let Some(shape_device) = self.shape_device.as_ref() {
+            shape_device.set_shape(serial, cursor_shape(cursor));
+            return;
+        }
+        self.show_themed_cursor(serial, themes, cursor_names(cursor), window.buffer_scale());
+    }
+
+    /// Show the first of `names` which is in the cursor theme
+    fn show_themed_cursor(
+        &self,
+        serial: u32,
+        themes: &mut CursorThemes,
+        names: &[&str],
+        scale: u32,
+    ) {
+        let Some(image) = themes.image(names, scale) else {
+            tracing::warn!("unable to load cursor {:?} from the cursor theme", names[0]);
+            return;
+        };
+        let (width, height) = image.dimensions();
+        let (hot_x, hot_y) = image.hotspot();
+        // The theme might not have an image of the size we asked for
+        let scale = buffer_scale((width, height), scale);
+        let surface = &self.cursor_surface;
+        surface.set_buffer_scale(scale as i32);
+        surface.attach(Some(image), 0, 0);
+        surface.damage(0, 0, (width / scale) as i32, (height / scale) as i32);
+        surface.commit();
+        self.pointer.set_cursor(
+            serial,
+            Some(surface),
+            (hot_x / scale) as i32,
+            (hot_y / scale) as i32,
+        );
+    }
+
+    /// Tell the decorations the pointer is over that it has moved
+    // See `update_cursor` for why this is fine
+    #[allow(clippy::mutable_key_type)]
+    fn frame_moved(&mut self, windows: &mut Windows, time: Duration) {
+        let Some(frame_focus) = self.frame_focus.as_mut() else {
+            return;
+        };
+        let Some(window) = windows.get_mut(&frame_focus.window) else {
+            return;
+        };
+        if let Some(icon) = window.frame_pointer_moved(&frame_focus.surface, self.pos, time) {
+            frame_focus.icon = icon;
+        }
+    }
+
+    fn event(&self, modifiers: Modifiers, button: PointerButton) -> PointerEvent {
+        PointerEvent {
+            pointer_id: self.id,
+            // There is only ever a single pointer per seat, which is
+            // also what the seat's cursor follows
+            is_primary: true,
+            pointer_type: PointerType::Mouse(MouseInfo {
+                wheel_delta: Vec2::ZERO,
+            }),
+            pos: self.pos,
+            buttons: self.buttons,
+            modifiers,
+            button,
+            focus: false,
+            count: 0,
+        }
+    }
+
+    /// Send the scroll accumulated in this frame (if any) to the focused window
+    // See `update_cursor` for why this is fine
+    #[allow(clippy::mutable_key_type)]
+    fn flush_scroll(&mut self, modifiers: Modifiers, windows: &mut Windows) {
+        let scroll = std::mem::take(&mut self.pending_scroll);
+        let discrete = std::mem::take(&mut self.pending_discrete);
+        // Prefer the discrete steps where we have them, as they are more consistent
+        // between compositors. Continuous values (e.g. from touchpads) are in surface
+        // coordinates, which are already display points.
+        let pick = |discrete: f64, continuous: f64| {
+            if discrete != 0.0 {
+                discrete * WHEEL_DELTA_PER_STEP
+            } else {
+                continuous
+            }
+        };
+        let wheel_delta = Vec2::new(pick(discrete.x, scroll.x), pick(discrete.y, scroll.y));
+        if wheel_delta == Vec2::ZERO {
+            return;
+        }
+        let mut event = self.event(modifiers, PointerButton::None);
+        event.pointer_type = PointerType::Mouse(MouseInfo { wheel_delta });
+        if let Some(handler) = focused_handler(windows, self.focus.as_ref()) {
+            handler.wheel(&event);
+        }
+    }
+}
+
+impl Drop for PointerState {
+    fn drop(&mut self) {
+        if let Some(shape_device) = self.shape_device.take() {
+            shape_device.destroy();
+        }
+        self.cursor_surface.destroy();
+        self.pointer.release()
+    }
+}
+
+impl WaylandState {
+    /// Update the cursor of every pointer which is over `window`, such as when
+    /// the cursor of that window has changed
+    pub(in crate::backend::wayland) fn update_cursors(&mut self, window: &WindowId) {
+        for seat in &self.input_states {
+            let Some(pointer) = seat.pointer_state.as_ref() else {
+                continue;
+            };
+            if pointer.focus.as_ref() == Some(window) {
+                pointer.update_cursor(&self.windows, &mut self.cursor_themes);
+            }
+        }
+    }
+}
+
+/// Get the handler of the window the input is focused on, if that window still exists
+// See `update_cursor` for why this is fine
+#[allow(clippy::mutable_key_type)]
+pub(super) fn focused_handler<'a>(
+    windows: &'a mut Windows,
+    focus: Option<&WindowId>,
+) -> Option<&'a mut dyn WinHandler> {
+    let window = windows.get_mut(focus?)?;
+    Some(&mut *window.handler)
+}
+
+// Converts from a Linux input event code, as used by `wl_pointer.button`
+fn pointer_button(button: u32) -> PointerButton {
+    match button {
+        BTN_LEFT => PointerButton::Primary,
+        BTN_RIGHT => PointerButton::Secondary,
+        BTN_MIDDLE => PointerButton::Auxiliary,
+        BTN_SIDE | BTN_BACK => PointerButton::X1,
+        BTN_EXTRA | BTN_FORWARD => PointerButton::X2,
+        _ => {
+            tracing::warn!("unknown pointer button code {}", button);
+            PointerButton::None
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, PointerUserData> for WaylandState {
+    // See `update_cursor` for why this is fine
+    #[allow(clippy::mutable_key_type)]
+    fn event(
+        state: &mut Self,
+        proxy: &wl_pointer::WlPointer,
+        event: <wl_pointer::WlPointer as Proxy>::Event,
+        data: &PointerUserData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let seat = input_state(&mut state.input_states, data.0);
+        let modifiers = seat.modifiers();
+        if let wl_pointer::Event::Button { serial, .. } = event {
+            seat.latest_serial = Some(serial);
+        }
+        let Some(pointer) = seat.pointer_state.as_mut() else {
+            // The pointer capability was removed, but we haven't caught up yet
+            return;
+        };
+        let windows = &mut state.windows;
+        match event {
+            wl_pointer::Event::Enter {
+                serial,
+                surface,
+                surface_x,
+                surface_y,
+            } => {
+                pointer.pos = Point::new(surface_x, surface_y);
+                // The cursor must be set again on every enter
+                pointer.enter_serial = Some(serial);
+                pointer.menu_focus = None;
+                let menu_scale = state
+                    .context_menu
+                    .as_ref()
+                    .filter(|menu| menu.level_of(&surface).is_some())
+                    .map(ContextMenu::scale);
+                if let Some(scale) = menu_scale {
+                    pointer.menu_focus = Some(MenuFocus {
+                        surface: surface.clone(),
+                        scale,
+                    });
+                    pointer.update_cursor(windows, &mut state.cursor_themes);
+                    let pos = pointer.pos;
+                    state.context_menu_pointer_moved(Some(&surface), pos);
+                    return;
+                }
+                if let Some(window) = WindowId::of_decorations(&surface) {
+                    pointer.frame_focus = Some(FrameFocus {
+                        window,
+                        surface,
+                        icon: CursorIcon::Default,
+                    });
+                    pointer.frame_moved(windows, Duration::ZERO);
+                    pointer.update_cursor(windows, &mut state.cursor_themes);
+                    return;
+                }
+                pointer.focus = Some(WindowId::of_surface(&surface));
+                // Buttons pressed outside of our surfaces are not reported to us
+                pointer.buttons = PointerButtons::new();
+                pointer.update_cursor(windows, &mut state.cursor_themes);
+                let event = pointer.event(modifiers, PointerButton::None);
+                if let Some(handler) = focused_handler(windows, pointer.focus.as_ref()) {
+                    handler.pointer_move(&event);
+                }
+            }
+            wl_pointer::Event::Leave { .. } => {
+                if pointer.menu_focus.take().is_some() {
+                    state.context_menu_pointer_moved(None, Point::ZERO);
+                    return;
+                }
+                if let Some(frame_focus) = pointer.frame_focus.take() {
+                    if let Some(window) = windows.get_mut(&frame_focus.window) {
+                        window.frame_pointer_left();
+                    }
+                    return;
+                }
+                pointer.pending_scroll = Vec2::ZERO;
+                pointer.pending_discrete = Vec2::ZERO;
+                let focus = pointer.focus.take();
+                if let Some(handler) = focused_handler(windows, focus.as_ref()) {
+                    handler.pointer_leave();
+                }
+            }
+            wl_pointer::Event::Motion {
+                time,
+                surface_x,
+                surface_y,
+            } => {
+                pointer.pos = Point::new(surface_x, surface_y);
+                if let Some(menu_focus) = pointer.menu_focus.as_ref() {
+                    let (surface, pos) = (menu_focus.surface.clone(), pointer.pos);
+                    state.context_menu_pointer_moved(Some(&surface), pos);
+                    return;
+                }
+                if pointer.frame_focus.is_some() {
+                    pointer.frame_moved(windows, Duration::from_millis(time.into()));
+                    pointer.update_cursor(windows, &mut state.cursor_themes);
+                    return;
+                }
+                let event = pointer.event(modifiers, PointerButton::None);
+                if let Some(handler) = focused_handler(windows, pointer.focus.as_ref()) {
+                    handler.pointer_move(&event);
+                }
+            }
+            wl_pointer::Event::Button {
+                serial,
+                time,
+                button,
+                state: button_state,
+            } => {
+                let pressed =
+                    matches!(button_state, WEnum::Value(wl_pointer::ButtonState::Pressed));
+                if let Some(menu_focus) = pointer.menu_focus.as_ref() {
+                    let (surface, pos) = (menu_focus.surface.clone(), pointer.pos);
+                    if pressed {
+                        state.context_menu_button_pressed(Some(&surface));
+                    } else {
+                        state.context_menu_button_released(&surface, pos);
+                    }
+                    return;
+                }
+                if pressed && state.context_menu.is_some() {
+                    // Clicking on one of our windows closes the menu, like clicking elsewhere
+                    state.context_menu_button_pressed(None);
+                    return;
+                }
+                if let Some(frame_focus) = pointer.frame_focus.as_ref() {
+                    let click = match button {
+                        BTN_LEFT => FrameClick::Normal,
+                        BTN_RIGHT => FrameClick::Alternate,
+                        _ => return,
+                    };
+                    if let Some(window) = windows.get_mut(&frame_focus.window) {
+                        window.frame_click(
+                            click,
+                            pressed,
+                            Duration::from_millis(time.into()),
+                            &seat.seat,
+                            serial,
+                        );
+                    }
+                    return;
+                }
+                let button = pointer_button(button);
+                match button_state {
+                    WEnum::Value(wl_pointer::ButtonState::Pressed) => {
+                        // Like on X11, the pressed button is included in `buttons`
+                        pointer.buttons.insert(button);
+                        pointer.press_serial = Some(serial);
+                        let mut event = pointer.event(modifiers, button);
+                        event.count = pointer.click_counter.count_for_click(pointer.pos);
+                        if let Some(handler) = focused_handler(windows, pointer.focus.as_ref()) {
+                            handler.pointer_down(&event);
+                        }
+                    }
+                    WEnum::Value(wl_pointer::ButtonState::Released) => {
+                        pointer.buttons.remove(button);
+                        let event = pointer.event(modifiers, button);
+                        if let Some(handler) = focused_handler(windows, pointer.focus.as_ref()) {
+                            handler.pointer_up(&event);
+                        }
+                    }
+                    WEnum::Value(it) => {
+                        tracing::warn!(pointer = ?proxy, "unsupported button state {it:?}");
+                    }
+                    WEnum::Unknown(it) => {
+                        tracing::warn!(pointer = ?proxy, "unknown button state {it}");
+                    }
+                }
+            }
+            wl_pointer::Event::Axis {
+                time: _,
+                axis,
+                value,
+            } => {
+                match axis {
+                    WEnum::Value(wl_pointer::Axis::VerticalScroll) => {
+                        pointer.pending_scroll.y += value
+                    }
+                    WEnum::Value(wl_pointer::Axis::HorizontalScroll) => {
+                        pointer.pending_scroll.x += value;
+                    }
+                    _ => tracing::warn!(pointer = ?proxy, "unknown scroll axis {axis:?}"),
+                }
+                // Before version 5, there are no `frame` events to group axis events
+                if proxy.version() < wl_pointer::EVT_FRAME_SINCE {
+                    pointer.flush_scroll(modifiers, windows);
+                }
+            }
+            wl_pointer::Event::AxisDiscrete { axis, discrete } => match axis {
+                WEnum::Value(wl_pointer::Axis::VerticalScroll) => {
+                    pointer.pending_discrete.y += discrete as f64;
+                }
+                WEnum::Value(wl_pointer::Axis::HorizontalScroll) => {
+                    pointer.pending_discrete.x += discrete as f64;
+                }
+                _ => tracing::warn!(pointer = ?proxy, "unknown scroll axis {axis:?}"),
+            },
+            wl_pointer::Event::Frame => pointer.flush_scroll(modifiers, windows),
+            // We don't currently distinguish between kinetic and non-kinetic scrolling
+            wl_pointer::Event::AxisSource { .. } | wl_pointer::Event::AxisStop { .. } => {}
+            _ => {}
+        }
+    }
+}
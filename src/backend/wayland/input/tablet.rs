@@ -0,0 +1,387 @@
+//! Support for graphics tablets, using the `zwp_tablet_v2` protocol.
+//!
+//! Pads (the buttons, rings and strips on the tablet itself) are not currently reported
+//! to the application, but we still need to accept their objects from the compositor.
+
+use smithay_client_toolkit::reexports::{
+    client::{
+        event_created_child, protocol::wl_seat, Connection, Dispatch, Proxy, QueueHandle, WEnum,
+    },
+    protocols::wp::tablet::zv2::client::{
+        zwp_tablet_manager_v2::ZwpTabletManagerV2,
+        zwp_tablet_pad_group_v2::{self, ZwpTabletPadGroupV2},
+        zwp_tablet_pad_ring_v2::ZwpTabletPadRingV2,
+        zwp_tablet_pad_strip_v2::ZwpTabletPadStripV2,
+        zwp_tablet_pad_v2::{self, ZwpTabletPadV2},
+        zwp_tablet_seat_v2::{self, ZwpTabletSeatV2},
+        zwp_tablet_tool_v2::{self, ZwpTabletToolV2},
+        zwp_tablet_v2::{self, ZwpTabletV2},
+    },
+};
+
+use crate::{
+    backend::wayland::window::WindowId,
+    common_util::ClickCounter,
+    kurbo::Point,
+    pointer::{Angle, PenInclination, PenInfo, PointerId, PointerType},
+    Counter, Modifiers, PointerButton, PointerButtons, PointerEvent,
+};
+
+use super::{input_state, pointer::focused_handler, SeatName, WaylandState, Windows};
+
+// Linux input event codes for stylus buttons, from `linux/input-event-codes.h`
+const BTN_STYLUS: u32 = 0x14b;
+const BTN_STYLUS2: u32 = 0x14c;
+const BTN_STYLUS3: u32 = 0x149;
+
+/// The maximum value of the normalized pressure and slider axes
+const AXIS_MAX: f64 = 65535.0;
+
+static TOOL_COUNTER: Counter = Counter::new();
+
+pub(crate) struct TabletManagerData;
+
+/// The seat identifier of this tablet seat
+struct TabletSeatUserData(SeatName);
+
+/// The tablets and tools which are associated with a seat
+pub(super) struct TabletSeatState {
+    tablet_seat: ZwpTabletSeatV2,
+    tools: Vec<TabletTool>,
+}
+
+/// The changes to a tool which should be reported at the end of the current frame
+#[derive(Default)]
+struct PendingToolFrame {
+    moved: bool,
+    /// Whether the tip was put down (`true`) or lifted (`false`)
+    tip: Option<bool>,
+    /// The stylus buttons which were pressed (`true`) or released (`false`)
+    buttons: Vec<(PointerButton, bool)>,
+    left: bool,
+}
+
+struct TabletTool {
+    tool: ZwpTabletToolV2,
+    id: PointerId,
+    is_eraser: bool,
+    /// The window which the tool is in proximity of, if any
+    focus: Option<WindowId>,
+    /// The position of the tool within `focus`, in display points
+    pos: Point,
+    pen_info: PenInfo,
+    buttons: PointerButtons,
+    click_counter: ClickCounter,
+    pending: PendingToolFrame,
+}
+
+impl TabletSeatState {
+    pub(super) fn new(
+        manager: &ZwpTabletManagerV2,
+        seat: &wl_seat::WlSeat,
+        qh: &QueueHandle<WaylandState>,
+        seat_name: SeatName,
+    ) -> Self {
+        TabletSeatState {
+            tablet_seat: manager.get_tablet_seat(seat, qh, TabletSeatUserData(seat_name)),
+            tools: Vec::new(),
+        }
+    }
+
+    fn tool(&mut self, tool: &ZwpTabletToolV2) -> Option<&mut TabletTool> {
+        self.tools.iter_mut().find(|it| &it.tool == tool)
+    }
+}
+
+impl Drop for TabletSeatState {
+    fn drop(&mut self) {
+        for tool in &self.tools {
+            tool.tool.destroy();
+        }
+        self.tablet_seat.destroy();
+    }
+}
+
+impl TabletTool {
+    fn new(tool: ZwpTabletToolV2, seat: SeatName) -> Self {
+        TabletTool {
+            tool,
+            // Keep tool ids distinct from the mouse pointer and touch points of the seat
+            id: PointerId(seat.0 << 32 | 1 << 31 | (TOOL_COUNTER.next() & 0x7fff_ffff)),
+            is_eraser: false,
+            focus: None,
+            pos: Point::ZERO,
+            pen_info: PenInfo {
+                pressure: 0.0,
+                tangential_pressure: 0.0,
+                inclination: PenInclination::default(),
+                twist: Angle::degrees(0.0),
+            },
+            buttons: PointerButtons::new(),
+            click_counter: ClickCounter::default(),
+            pending: PendingToolFrame::default(),
+        }
+    }
+
+    fn event(&self, modifiers: Modifiers, button: PointerButton) -> PointerEvent {
+        let pointer_type = if self.is_eraser {
+            PointerType::Eraser(self.pen_info.clone())
+        } else {
+            PointerType::Pen(self.pen_info.clone())
+        };
+        PointerEvent {
+            pointer_id: self.id,
+            // Like on X11, tablet tools drive the seat's cursor
+            is_primary: true,
+            pointer_type,
+            pos: self.pos,
+            buttons: self.buttons,
+            modifiers,
+            button,
+            focus: false,
+            count: 0,
+        }
+    }
+
+    /// Report all the changes accumulated during this frame
+    // `Windows` is keyed by `WindowId`, which is fine as in `PointerState::update_cursor`
+    #[allow(clippy::mutable_key_type)]
+    fn frame(&mut self, modifiers: Modifiers, windows: &mut Windows) {
+        let pending = std::mem::take(&mut self.pending);
+        let focus = if pending.left {
+            self.focus.take()
+        } else {
+            self.focus.clone()
+        };
+        let Some(handler) = focused_handler(windows, focus.as_ref()) else {
+            return;
+        };
+        if pending.moved {
+            handler.pointer_move(&self.event(modifiers, PointerButton::None));
+        }
+        // The tip touching the surface acts as the primary button
+        let buttons = pending.tip.map(|down| (PointerButton::Primary, down));
+        for (button, pressed) in buttons.into_iter().chain(pending.buttons) {
+            if pressed {
+                // Like for the mouse, the pressed button is included in `buttons`
+                self.buttons.insert(button);
+                let mut event = self.event(modifiers, button);
+                event.count = self.click_counter.count_for_click(self.pos);
+                handler.pointer_down(&event);
+            } else {
+                self.buttons.remove(button);
+                handler.pointer_up(&self.event(modifiers, button));
+            }
+        }
+        if pending.left {
+            handler.pointer_leave();
+        }
+    }
+}
+
+// Converts from a Linux input event code, as used by `zwp_tablet_tool_v2.button`
+fn stylus_button(button: u32) -> PointerButton {
+    match button {
+        BTN_STYLUS => PointerButton::Secondary,
+        BTN_STYLUS2 => PointerButton::Auxiliary,
+        BTN_STYLUS3 => PointerButton::X1,
+        _ => {
+            tracing::warn!("unknown stylus button code {}", button);
+            PointerButton::None
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletToolV2, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwpTabletToolV2,
+        event: <ZwpTabletToolV2 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // Tools are created by the compositor, so can't be given the seat name as user data
+        let Some(seat) = state.input_states.iter_mut().find(|seat| {
+            seat.tablet_state
+                .as_ref()
+                .is_some_and(|it| it.tools.iter().any(|it| &it.tool == proxy))
+        }) else {
+            tracing::warn!(tool = ?proxy, "got event for unknown tablet tool");
+            return;
+        };
+        let modifiers = seat.modifiers();
+        let tablet_seat = seat.tablet_state.as_mut().unwrap();
+        let tool = tablet_seat.tool(proxy).unwrap();
+        match event {
+            zwp_tablet_tool_v2::Event::Type { tool_type } => {
+                tool.is_eraser = tool_type == WEnum::Value(zwp_tablet_tool_v2::Type::Eraser);
+            }
+            zwp_tablet_tool_v2::Event::ProximityIn { surface, .. } => {
+                tool.focus = Some(WindowId::of_surface(&surface));
+                tool.buttons = PointerButtons::new();
+            }
+            zwp_tablet_tool_v2::Event::ProximityOut => tool.pending.left = true,
+            zwp_tablet_tool_v2::Event::Down { .. } => tool.pending.tip = Some(true),
+            zwp_tablet_tool_v2::Event::Up => tool.pending.tip = Some(false),
+            zwp_tablet_tool_v2::Event::Motion { x, y } => {
+                tool.pos = Point::new(x, y);
+                tool.pending.moved = true;
+            }
+            zwp_tablet_tool_v2::Event::Pressure { pressure } => {
+                tool.pen_info.pressure = pressure as f64 / AXIS_MAX;
+                tool.pending.moved = true;
+            }
+            zwp_tablet_tool_v2::Event::Tilt { tilt_x, tilt_y } => {
+                tool.pen_info.inclination =
+                    PenInclination::from_tilt(tilt_x, tilt_y).unwrap_or_default();
+                tool.pending.moved = true;
+            }
+            zwp_tablet_tool_v2::Event::Rotation { degrees } => {
+                tool.pen_info.twist = Angle::degrees(degrees);
+                tool.pending.moved = true;
+            }
+            zwp_tablet_tool_v2::Event::Slider { position } => {
+                tool.pen_info.tangential_pressure = position as f64 / AXIS_MAX;
+                tool.pending.moved = true;
+            }
+            zwp_tablet_tool_v2::Event::Button {
+                button,
+                state: button_state,
+                ..
+            } => {
+                let pressed =
+                    button_state == WEnum::Value(zwp_tablet_tool_v2::ButtonState::Pressed);
+                tool.pending.buttons.push((stylus_button(button), pressed));
+            }
+            zwp_tablet_tool_v2::Event::Frame { .. } => tool.frame(modifiers, &mut state.windows),
+            zwp_tablet_tool_v2::Event::Removed => {
+                tool.tool.destroy();
+                let proxy = proxy.clone();
+                tablet_seat.tools.retain(|it| it.tool != proxy);
+            }
+            // We don't use the hardware details of the tool, or report distance or wheel
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletSeatV2, TabletSeatUserData> for WaylandState {
+    event_created_child!(WaylandState, ZwpTabletSeatV2, [
+        zwp_tablet_seat_v2::EVT_TABLET_ADDED_OPCODE => (ZwpTabletV2, ()),
+        zwp_tablet_seat_v2::EVT_TOOL_ADDED_OPCODE => (ZwpTabletToolV2, ()),
+        zwp_tablet_seat_v2::EVT_PAD_ADDED_OPCODE => (ZwpTabletPadV2, ()),
+    ]);
+
+    fn event(
+        state: &mut Self,
+        _: &ZwpTabletSeatV2,
+        event: <ZwpTabletSeatV2 as Proxy>::Event,
+        data: &TabletSeatUserData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_tablet_seat_v2::Event::ToolAdded { id } => {
+                let seat = input_state(&mut state.input_states, data.0);
+                if let Some(tablet_seat) = seat.tablet_state.as_mut() {
+                    tablet_seat.tools.push(TabletTool::new(id, data.0));
+                } else {
+                    id.destroy();
+                }
+            }
+            // Tablets only describe the hardware, so there's nothing we need to track about them
+            zwp_tablet_seat_v2::Event::TabletAdded { .. } => {}
+            zwp_tablet_seat_v2::Event::PadAdded { .. } => {}
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletV2, ()> for WaylandState {
+    fn event(
+        _: &mut Self,
+        proxy: &ZwpTabletV2,
+        event: <ZwpTabletV2 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_tablet_v2::Event::Removed = event {
+            proxy.destroy();
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletPadV2, ()> for WaylandState {
+    event_created_child!(WaylandState, ZwpTabletPadV2, [
+        zwp_tablet_pad_v2::EVT_GROUP_OPCODE => (ZwpTabletPadGroupV2, ()),
+    ]);
+
+    fn event(
+        _: &mut Self,
+        proxy: &ZwpTabletPadV2,
+        event: <ZwpTabletPadV2 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_tablet_pad_v2::Event::Removed = event {
+            proxy.destroy();
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletPadGroupV2, ()> for WaylandState {
+    event_created_child!(WaylandState, ZwpTabletPadGroupV2, [
+        zwp_tablet_pad_group_v2::EVT_RING_OPCODE => (ZwpTabletPadRingV2, ()),
+        zwp_tablet_pad_group_v2::EVT_STRIP_OPCODE => (ZwpTabletPadStripV2, ()),
+    ]);
+
+    fn event(
+        _: &mut Self,
+        _: &ZwpTabletPadGroupV2,
+        _: <ZwpTabletPadGroupV2 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwpTabletPadRingV2, ()> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &ZwpTabletPadRingV2,
+        _: <ZwpTabletPadRingV2 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwpTabletPadStripV2, ()> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &ZwpTabletPadStripV2,
+        _: <ZwpTabletPadStripV2 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwpTabletManagerV2, TabletManagerData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &ZwpTabletManagerV2,
+        event: <ZwpTabletManagerV2 as Proxy>::Event,
+        _: &TabletManagerData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        tracing::error!(?event, "unexpected zwp_tablet_manager_v2 event");
+    }
+}
@@ -0,0 +1,235 @@
+use smithay_client_toolkit::reexports::client::{
+    protocol::{wl_seat, wl_touch},
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+
+use crate::{
+    backend::wayland::window::WindowId,
+    kurbo::{Point, Size},
+    pointer::{PointerId, PointerType, TouchInfo},
+    Modifiers, PointerButton, PointerButtons, PointerEvent,
+};
+
+use super::{input_state, pointer::focused_handler, SeatName, WaylandState, Windows};
+
+/// The seat identifier of this touch device
+struct TouchUserData(SeatName);
+
+/// What happened to a touch point since the last `frame` event
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TouchPhase {
+    Down,
+    Motion,
+    Up,
+}
+
+struct TouchPoint {
+    /// The touch slot id from the compositor. This is reused once the point is lifted
+    slot: i32,
+    /// The window which this touch point started in. All events of the point are sent there
+    window: WindowId,
+    /// The position of the point within `window`, in display points
+    pos: Point,
+    contact_geometry: Size,
+    is_primary: bool,
+    /// The change which needs to be reported at the end of the current frame, if any
+    pending: Option<TouchPhase>,
+}
+
+pub(super) struct TouchState {
+    touch: wl_touch::WlTouch,
+    seat: SeatName,
+    /// The currently active touch points, in the order they were put down
+    points: Vec<TouchPoint>,
+}
+
+impl TouchPoint {
+    fn event(&self, seat: SeatName, modifiers: Modifiers) -> PointerEvent {
+        // Like on X11, only the primary touch point acts as a (primary) button press
+        let button = if self.is_primary {
+            PointerButton::Primary
+        } else {
+            PointerButton::None
+        };
+        let (buttons, button) = match self.pending {
+            Some(TouchPhase::Down) => (PointerButtons::new().with(button), button),
+            Some(TouchPhase::Motion) | None => {
+                (PointerButtons::new().with(button), PointerButton::None)
+            }
+            Some(TouchPhase::Up) => (PointerButtons::new(), button),
+        };
+        PointerEvent {
+            // Keep touch ids distinct from the seat's mouse pointer, whose id is the seat name
+            pointer_id: PointerId(seat.0 << 32 | self.slot as u32 as u64),
+            is_primary: self.is_primary,
+            pointer_type: PointerType::Touch(TouchInfo {
+                contact_geometry: self.contact_geometry,
+                pressure: 0.0,
+            }),
+            pos: self.pos,
+            buttons,
+            modifiers,
+            button,
+            focus: false,
+            count: 0,
+        }
+    }
+
+    /// Send the pending change of this point (if any) to its window
+    // `Windows` is keyed by `WindowId`, which is fine as in `PointerState::update_cursor`
+    #[allow(clippy::mutable_key_type)]
+    fn report(&self, seat: SeatName, modifiers: Modifiers, windows: &mut Windows) {
+        let Some(phase) = self.pending else {
+            return;
+        };
+        let event = self.event(seat, modifiers);
+        let Some(handler) = focused_handler(windows, Some(&self.window)) else {
+            return;
+        };
+        match phase {
+            TouchPhase::Down => handler.pointer_down(&event),
+            TouchPhase::Motion => handler.pointer_move(&event),
+            TouchPhase::Up => handler.pointer_up(&event),
+        }
+    }
+}
+
+impl TouchState {
+    pub(super) fn new(
+        qh: &QueueHandle<WaylandState>,
+        name: SeatName,
+        seat: wl_seat::WlSeat,
+    ) -> Self {
+        TouchState {
+            touch: seat.get_touch(qh, TouchUserData(name)),
+            seat: name,
+            points: Vec::new(),
+        }
+    }
+
+    fn point(&mut self, slot: i32) -> Option<&mut TouchPoint> {
+        self.points.iter_mut().find(|it| it.slot == slot)
+    }
+
+    /// Report all the changes accumulated during this frame
+    // See `report`
+    #[allow(clippy::mutable_key_type)]
+    fn frame(&mut self, modifiers: Modifiers, windows: &mut Windows) {
+        for point in &self.points {
+            point.report(self.seat, modifiers, windows);
+        }
+        self.points
+            .retain(|point| point.pending != Some(TouchPhase::Up));
+        for point in &mut self.points {
+            point.pending = None;
+        }
+    }
+
+    /// The compositor has taken over this touch sequence (e.g. for a gesture), so we
+    /// won't get any further events for the current touch points
+    // See `report`
+    #[allow(clippy::mutable_key_type)]
+    fn cancel(&mut self, windows: &mut Windows) {
+        let mut cancelled: Vec<WindowId> = Vec::new();
+        for point in self.points.drain(..) {
+            if !cancelled.contains(&point.window) {
+                cancelled.push(point.window);
+            }
+        }
+        for window in cancelled {
+            if let Some(handler) = focused_handler(windows, Some(&window)) {
+                handler.pointer_leave();
+            }
+        }
+    }
+}
+
+impl Drop for TouchState {
+    fn drop(&mut self) {
+        self.touch.release()
+    }
+}
+
+impl Dispatch<wl_touch::WlTouch, TouchUserData> for WaylandState {
+    fn event(
+        state: &mut Self,
+        proxy: &wl_touch::WlTouch,
+        event: <wl_touch::WlTouch as Proxy>::Event,
+        data: &TouchUserData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let seat = input_state(&mut state.input_states, data.0);
+        let modifiers = seat.modifiers();
+        if let wl_touch::Event::Down { serial, .. } = event {
+            seat.latest_serial = Some(serial);
+        }
+        let Some(touch) = seat.touch_state.as_mut() else {
+            // The touch capability was removed, but we haven't caught up yet
+            return;
+        };
+        match event {
+            wl_touch::Event::Down {
+                serial: _,
+                time: _,
+                surface,
+                id,
+                x,
+                y,
+            } => {
+                let is_primary = touch.points.iter().all(|it| !it.is_primary);
+                touch.points.push(TouchPoint {
+                    slot: id,
+                    window: WindowId::of_surface(&surface),
+                    pos: Point::new(x, y),
+                    contact_geometry: Size::ZERO,
+                    is_primary,
+                    pending: Some(TouchPhase::Down),
+                });
+            }
+            wl_touch::Event::Up {
+                serial: _,
+                time: _,
+                id,
+            } => {
+                let seat = touch.seat;
+                match touch.point(id) {
+                    Some(point) => {
+                        // A point which was put down and lifted within a single frame
+                        // still needs to report being put down
+                        if point.pending == Some(TouchPhase::Down) {
+                            point.report(seat, modifiers, &mut state.windows);
+                        }
+                        point.pending = Some(TouchPhase::Up);
+                    }
+                    None => {
+                        tracing::warn!(touch = ?proxy, "got up event for unknown touch point {id}");
+                    }
+                }
+            }
+            wl_touch::Event::Motion { time: _, id, x, y } => match touch.point(id) {
+                Some(point) => {
+                    point.pos = Point::new(x, y);
+                    // If the point was only just put down, we still need to report that
+                    point.pending.get_or_insert(TouchPhase::Motion);
+                }
+                None => {
+                    tracing::warn!(touch = ?proxy, "got motion event for unknown touch point {id}");
+                }
+            },
+            wl_touch::Event::Shape { id, major, minor } => {
+                if let Some(point) = touch.point(id) {
+                    // With an orientation of zero, the major axis is aligned with the
+                    // surface's y axis. We don't track orientation, so report it that way
+                    point.contact_geometry = Size::new(minor, major);
+                    point.pending.get_or_insert(TouchPhase::Motion);
+                }
+            }
+            wl_touch::Event::Frame => touch.frame(modifiers, &mut state.windows),
+            wl_touch::Event::Cancel => touch.cancel(&mut state.windows),
+            // We don't report the orientation of the contact ellipse
+            wl_touch::Event::Orientation { .. } => {}
+            _ => {}
+        }
+    }
+}
@@ -12,43 +12,360 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#![allow(unused)]
-use super::window::WindowHandle;
-use crate::common_util::strip_access_key;
-use crate::hotkey::{HotKey, RawMods};
-use crate::keyboard::{KbKey, Modifiers};
+//! Wayland menus implementation.
+//!
+//! Wayland has no native menus, so we draw context menus ourselves into `xdg_popup`s,
+//! which grab the input of the seat while the menu is open.
 
-#[derive(Default, Debug)]
-pub struct Menu;
+use anyhow::{anyhow, Error};
+use smithay_client_toolkit::reexports::client::protocol::wl_shm;
+use smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface;
+use smithay_client_toolkit::reexports::protocols::xdg::shell::client::xdg_positioner::{
+    Anchor, ConstraintAdjustment, Gravity,
+};
+use smithay_client_toolkit::shell::xdg::popup::Popup;
+use smithay_client_toolkit::shell::xdg::{XdgPositioner, XdgShell};
+use smithay_client_toolkit::shm::slot::{Buffer, SlotPool};
 
-#[derive(Debug)]
-struct MenuItem;
+use super::cursor::argb32_to_shm;
+use super::window::{popup_positioner, WindowId};
+use super::WaylandState;
+use crate::backend::shared::menu::{self, MenuAction, MenuTracker};
+use crate::hotkey::HotKey;
+use crate::kurbo::{Point, Size};
+use crate::KbKey;
+
+pub struct Menu(menu::Menu);
 
 impl Menu {
     pub fn new() -> Menu {
-        Menu
+        Menu(menu::Menu::new())
     }
 
     pub fn new_for_popup() -> Menu {
-        Menu
+        Menu(menu::Menu::new())
     }
 
-    pub fn add_dropdown(&mut self, menu: Menu, text: &str, _enabled: bool) {
-        tracing::warn!("unimplemented");
+    pub fn add_dropdown(&mut self, menu: Menu, text: &str, enabled: bool) {
+        self.0.add_dropdown(menu.0, text, enabled);
     }
 
     pub fn add_item(
         &mut self,
-        _id: u32,
-        _text: &str,
-        _key: Option<&HotKey>,
-        _selected: Option<bool>,
-        _enabled: bool,
+        id: u32,
+        text: &str,
+        key: Option<&HotKey>,
+        selected: Option<bool>,
+        enabled: bool,
     ) {
-        tracing::warn!("unimplemented");
+        self.0.add_item(id, text, key, selected, enabled);
     }
 
     pub fn add_separator(&mut self) {
-        tracing::warn!("unimplemented");
+        self.0.add_separator();
+    }
+}
+
+/// A context menu which is open on screen, stored in [`WaylandState`]
+pub(super) struct ContextMenu {
+    tracker: MenuTracker,
+    /// The window which opened the menu, which is sent the chosen command
+    owner: WindowId,
+    /// The position of the root menu relative to `owner`, in display points
+    origin: Point,
+    /// The popups showing each open menu of `tracker`
+    popups: Vec<MenuPopup>,
+    /// The memory which the menus are drawn into
+    pool: SlotPool,
+    /// The integer scale the menus are drawn at
+    scale: u32,
+}
+
+/// A popup showing one of the open menus
+struct MenuPopup {
+    popup: Popup,
+    /// The `MenuLevel::parent` of the menu this popup shows
+    parent: Option<usize>,
+    /// The hovered item when the popup was last drawn
+    hovered: Option<usize>,
+    /// Whether the compositor has configured the popup, so that it can be drawn
+    configured: bool,
+    /// The buffer currently attached to the popup's surface
+    buffer: Option<Buffer>,
+}
+
+impl ContextMenu {
+    /// The window which opened the menu
+    pub(super) fn owner(&self) -> &WindowId {
+        &self.owner
+    }
+
+    /// The open menu shown on `surface`, if it is part of this menu
+    pub(super) fn level_of(&self, surface: &WlSurface) -> Option<usize> {
+        self.popups
+            .iter()
+            .position(|popup| popup.popup.wl_surface() == surface)
+    }
+
+    /// The integer scale the menus are drawn at
+    pub(super) fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Make the popups match the open menus of the tracker, and redraw any which changed.
+    fn update(&mut self, state: &WaylandState) -> Result<(), Error> {
+        let levels = self.tracker.levels();
+        let unchanged = self
+            .popups
+            .iter()
+            .zip(levels)
+            .take_while(|(popup, level)| popup.parent == level.parent)
+            .count();
+        // Nested popups must be destroyed from the top down
+        while self.popups.len() > unchanged {
+            self.popups.pop();
+        }
+
+        for (level, menu_level) in levels.iter().enumerate().skip(self.popups.len()) {
+            let menu = self.tracker.menu(level);
+            let (width, height) = menu.size(1);
+            let size = Size::new(width.into(), height.into());
+            let owner = state
+                .windows
+                .get(&self.owner)
+                .ok_or_else(|| anyhow!("the owner of the menu was closed"))?;
+            let owner_offset = owner.content_offset();
+            let (root_parent, xdg_shell) = owner
+                .popup_parent()
+                .ok_or_else(|| anyhow!("the owner of the menu was closed"))?;
+            let (parent, positioner) = match (self.popups.last(), menu_level.parent) {
+                (Some(parent), Some(index)) => {
+                    let parent_menu = self.tracker.menu(level - 1);
+                    let positioner = submenu_positioner(
+                        &xdg_shell,
+                        parent_menu.size(1).0,
+                        parent_menu.item_top(index, 1),
+                        menu.item_top(0, 1),
+                        size,
+                    )?;
+                    (parent.popup.xdg_surface(), positioner)
+                }
+                _ => (
+                    &root_parent,
+                    popup_positioner(&xdg_shell, self.origin, owner_offset, size)?,
+                ),
+            };
+            let surface = state.compositor_state.create_surface(&state.wayland_queue);
+            let popup = Popup::from_surface(
+                Some(parent),
+                &positioner,
+                &state.wayland_queue,
+                surface,
+                &*xdg_shell,
+            )?;
+            // The grab has to be taken before the popup is first committed
+            state.grab_popup(popup.xdg_popup());
+            popup.wl_surface().commit();
+            self.popups.push(MenuPopup {
+                popup,
+                parent: menu_level.parent,
+                // Drawn once the popup is configured
+                hovered: menu_level.hovered,
+                configured: false,
+                buffer: None,
+            });
+        }
+
+        for level in 0..self.popups.len() {
+            let hovered = self.tracker.levels()[level].hovered;
+            if self.popups[level].hovered != hovered {
+                self.popups[level].hovered = hovered;
+                self.draw(level);
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw the menu at `level` into a new buffer, and show it on its popup.
+    fn draw(&mut self, level: usize) {
+        let popup = &mut self.popups[level];
+        if !popup.configured {
+            return;
+        }
+        let (width, height) = self.tracker.menu(level).size(self.scale);
+        let mut pixels = vec![0; (width * height) as usize];
+        self.tracker.draw(level, &mut pixels, self.scale);
+        let (buffer, canvas) = match self.pool.create_buffer(
+            width as i32,
+            height as i32,
+            width as i32 * 4,
+            wl_shm::Format::Argb8888,
+        ) {
+            Ok(it) => it,
+            Err(err) => {
+                tracing::error!("failed to create a buffer for a menu: {err}");
+                return;
+            }
+        };
+        argb32_to_shm(&pixels, canvas);
+        let surface = popup.popup.wl_surface();
+        surface.set_buffer_scale(self.scale as i32);
+        if let Err(err) = buffer.attach_to(surface) {
+            tracing::error!("failed to attach the buffer of a menu: {err:?}");
+            return;
+        }
+        let scale = self.scale as i32;
+        surface.damage(0, 0, width as i32 / scale, height as i32 / scale);
+        surface.commit();
+        popup.buffer = Some(buffer);
+    }
+}
+
+impl Drop for ContextMenu {
+    fn drop(&mut self) {
+        // Nested popups must be destroyed from the top down
+        while self.popups.pop().is_some() {}
+    }
+}
+
+/// Create a positioner which places a submenu of `size` beside the item `item_top` points
+/// from the top of its parent menu, lining up the first item of the submenu (`first_top`
+/// points from its top) with that item
+fn submenu_positioner(
+    xdg_shell: &XdgShell,
+    parent_width: u32,
+    item_top: u32,
+    first_top: u32,
+    size: Size,
+) -> Result<XdgPositioner, Error> {
+    let positioner = XdgPositioner::new(xdg_shell)?;
+    positioner.set_size(size.width as i32, size.height as i32);
+    positioner.set_anchor_rect(0, item_top as i32, parent_width as i32, 1);
+    positioner.set_anchor(Anchor::TopRight);
+    positioner.set_gravity(Gravity::BottomRight);
+    positioner.set_offset(0, -(first_top as i32));
+    // Open to the left of the parent if there isn't room on the right
+    positioner
+        .set_constraint_adjustment(ConstraintAdjustment::FlipX | ConstraintAdjustment::SlideY);
+    Ok(positioner)
+}
+
+impl WaylandState {
+    /// Open `menu` at `pos` in the window `owner`, closing any menu which is already open
+    pub(super) fn show_context_menu(&mut self, owner: WindowId, menu: Menu, pos: Point) {
+        self.context_menu = None;
+        let Some(window) = self.windows.get(&owner) else {
+            return;
+        };
+        let pool = match SlotPool::new(4096, &self.shm) {
+            Ok(pool) => pool,
+            Err(err) => {
+                tracing::error!("failed to create memory pool for a menu: {err}");
+                return;
+            }
+        };
+        self.context_menu = Some(ContextMenu {
+            tracker: MenuTracker::new(menu.0),
+            owner,
+            origin: pos,
+            popups: Vec::new(),
+            pool,
+            scale: window.buffer_scale(),
+        });
+        self.update_context_menu();
+    }
+
+    /// Whether `surface` is one of the popups of the context menu
+    pub(super) fn is_context_menu(&self, surface: &WlSurface) -> bool {
+        self.context_menu
+            .as_ref()
+            .is_some_and(|menu| menu.level_of(surface).is_some())
+    }
+
+    /// Make the popups of the context menu match its state, closing it if that fails
+    fn update_context_menu(&mut self) {
+        let Some(mut menu) = self.context_menu.take() else {
+            return;
+        };
+        match menu.update(self) {
+            Ok(()) => self.context_menu = Some(menu),
+            Err(err) => tracing::error!("failed to show context menu: {err}"),
+        }
+    }
+
+    /// The compositor has configured `surface`, one of the popups of the context menu
+    pub(super) fn context_menu_configured(&mut self, surface: &WlSurface) {
+        let Some(menu) = self.context_menu.as_mut() else {
+            return;
+        };
+        if let Some(level) = menu.level_of(surface) {
+            if !menu.popups[level].configured {
+                menu.popups[level].configured = true;
+                menu.draw(level);
+            }
+        }
+    }
+
+    /// The pointer moved to `pos` on `surface`, which is part of the context menu, or left
+    /// the menu if `surface` is `None`
+    pub(super) fn context_menu_pointer_moved(&mut self, surface: Option<&WlSurface>, pos: Point) {
+        let Some(menu) = self.context_menu.as_mut() else {
+            return;
+        };
+        let level = surface.and_then(|surface| menu.level_of(surface));
+        let scale = menu.scale;
+        menu.tracker
+            .pointer_moved(level, pos.y * f64::from(scale), scale);
+        self.update_context_menu();
+    }
+
+    /// A button was pressed on `surface`, which may be outside of the context menu
+    pub(super) fn context_menu_button_pressed(&mut self, surface: Option<&WlSurface>) {
+        let Some(menu) = self.context_menu.as_mut() else {
+            return;
+        };
+        let level = surface.and_then(|surface| menu.level_of(surface));
+        let action = menu.tracker.button_pressed(level);
+        self.context_menu_action(action);
+    }
+
+    /// A button was released at `pos` on `surface`, which is part of the context menu
+    pub(super) fn context_menu_button_released(&mut self, surface: &WlSurface, pos: Point) {
+        let Some(menu) = self.context_menu.as_mut() else {
+            return;
+        };
+        let level = menu.level_of(surface);
+        let scale = menu.scale;
+        let action = menu
+            .tracker
+            .button_released(level, pos.y * f64::from(scale), scale);
+        self.context_menu_action(action);
+    }
+
+    /// `key` was pressed whilst the context menu had keyboard focus
+    pub(super) fn context_menu_key_down(&mut self, key: &KbKey) {
+        let Some(menu) = self.context_menu.as_mut() else {
+            return;
+        };
+        let action = menu.tracker.key_down(key);
+        self.context_menu_action(action);
+    }
+
+    /// Close the context menu, and tell its owner about the chosen command (if any)
+    fn context_menu_action(&mut self, action: Option<MenuAction>) {
+        let Some(action) = action else {
+            self.update_context_menu();
+            return;
+        };
+        let Some(menu) = self.context_menu.take() else {
+            return;
+        };
+        let owner = menu.owner.clone();
+        drop(menu);
+        if let MenuAction::Command(id) = action {
+            if let Some(window) = self.windows.get_mut(&owner) {
+                window.handler.command(id);
+            }
+        }
     }
 }
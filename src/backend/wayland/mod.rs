@@ -18,29 +18,37 @@ use std::{
     collections::HashMap,
     sync::{
         mpsc::{Receiver, Sender},
-        Arc,
+        Arc, RwLock,
     },
 };
 
 use smithay_client_toolkit::{
     compositor::CompositorState,
-    delegate_registry,
+    delegate_registry, delegate_shm, delegate_subcompositor,
     output::OutputState,
     reexports::{
         calloop::{channel, EventLoop, LoopHandle, LoopSignal},
-        client::QueueHandle,
-        protocols::wp::text_input::zv3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+        client::{protocol::wl_data_device_manager::WlDataDeviceManager, QueueHandle},
+        protocols::wp::{
+            cursor_shape::v1::client::wp_cursor_shape_manager_v1::WpCursorShapeManagerV1,
+            primary_selection::zv1::client::zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1,
+            tablet::zv2::client::zwp_tablet_manager_v2::ZwpTabletManagerV2,
+            text_input::zv3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+        },
     },
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
     seat::SeatState,
     shell::xdg::XdgShell,
+    shm::{Shm, ShmHandler},
 };
 
-use crate::{AppHandler, IdleToken};
+use crate::{AppHandler, IdleToken, Monitor};
 
 use self::{
+    cursor::CursorThemes,
     input::SeatInfo,
+    menu::ContextMenu,
     window::{WaylandWindowState, WindowAction, WindowId},
 };
 
@@ -48,6 +56,7 @@ use super::shared::xkb::Context;
 
 pub mod application;
 pub mod clipboard;
+mod cursor;
 pub mod error;
 mod input;
 pub mod menu;
@@ -74,13 +83,17 @@ struct WaylandState {
     // Drop the handler as early as possible, in case there are any Wgpu surfaces owned by it
     pub handler: Option<Box<dyn AppHandler>>,
     pub windows: HashMap<WindowId, WaylandWindowState>,
+    /// The context menu which is open, if any
+    pub context_menu: Option<ContextMenu>,
 
     pub registry_state: RegistryState,
 
     pub output_state: OutputState,
-    // TODO: Do we need to keep this around
-    // It is unused because(?) wgpu creates the surfaces through RawDisplayHandle(?)
-    pub _compositor_state: CompositorState,
+    // Shared with the `Application`, so that monitors can be accessed outside of the event loop
+    pub monitors: Arc<RwLock<Vec<Monitor>>>,
+    // Used to create the surfaces for cursors. Window surfaces are created
+    // through the `Application` instead
+    pub compositor_state: CompositorState,
     // Is used: Keep the XdgShell alive, which is a Weak in all Handles
     pub _xdg_shell_state: Arc<XdgShell>,
     pub wayland_queue: QueueHandle<Self>,
@@ -100,6 +113,14 @@ struct WaylandState {
     pub input_states: Vec<SeatInfo>,
     pub xkb_context: Context,
     pub text_input: Option<ZwpTextInputManagerV3>,
+    pub tablet_manager: Option<ZwpTabletManagerV2>,
+    pub data_device_manager: Option<WlDataDeviceManager>,
+    pub primary_selection_manager: Option<ZwpPrimarySelectionDeviceManagerV1>,
+    pub clipboard: clipboard::Clipboard,
+    pub primary_clipboard: clipboard::Clipboard,
+    pub shm: Shm,
+    pub cursor_shape_manager: Option<WpCursorShapeManagerV1>,
+    pub cursor_themes: CursorThemes,
 }
 
 delegate_registry!(WaylandState);
@@ -110,3 +131,12 @@ impl ProvidesRegistryState for WaylandState {
     }
     registry_handlers![OutputState, SeatState];
 }
+
+delegate_shm!(WaylandState);
+delegate_subcompositor!(WaylandState);
+
+impl ShmHandler for WaylandState {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
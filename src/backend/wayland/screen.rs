@@ -15,29 +15,118 @@
 //! wayland Monitors and Screen information.
 use smithay_client_toolkit::{
     delegate_output,
-    output::{OutputHandler, OutputState},
-    reexports::client::{protocol::wl_output::WlOutput, Connection, QueueHandle},
+    output::{OutputHandler, OutputInfo, OutputState},
+    reexports::client::{
+        protocol::wl_output::{Transform, WlOutput},
+        Connection, QueueHandle,
+    },
 };
 
+use crate::kurbo::{Point, Rect, Size};
 use crate::screen::Monitor;
+use crate::Scale;
 
-use super::WaylandState;
-
-pub(crate) fn get_monitors() -> Vec<Monitor> {
-    // There are a couple of things of note here.
-    // 1. This method does not take Application. This is not ideal.
-    // We can work around this
-    // We're not the only backend which has issues
-    // 2. Getting the data out of the WaylandState is unfortunately non-trivial
-    // The best way is probably to use an Arc based side-channel, although that's very ugly
-    // An alternative would be to pass something with a refernece to WaylandState into (all?)
-    // AppHandler/WinHandler methods. This also seems ugly
-    // Some of this ugliness stems from the mixing up of what Application means
-
-    // TODO: Implement this in the nasty way (side channel stored in Application and using
-    // Application::global)
-    tracing::warn!("get_monitors is not yet supported on wayland");
-    vec![]
+use super::{application::Application, WaylandState};
+
+pub(crate) fn get_monitors(app: &Application) -> Vec<Monitor> {
+    // The monitors are tracked by the `WaylandState`, which shares them with the `Application`
+    app.monitors.read().unwrap().clone()
+}
+
+impl WaylandState {
+    /// Recalculate the monitors shared with the `Application` from the current outputs,
+    /// ignoring `removed`, and tell the handler if they changed
+    fn update_monitors(&mut self, removed: Option<&WlOutput>) {
+        let infos = self
+            .output_state
+            .outputs()
+            .filter(|output| Some(output) != removed)
+            .filter_map(|output| self.output_state.info(&output))
+            .collect::<Vec<_>>();
+        // Wayland has no concept of a primary monitor, so we use the one at the origin, as
+        // that is what `Monitor::is_primary` promises
+        let primary = infos
+            .iter()
+            .position(|info| logical_rect(info).origin() == Point::ZERO)
+            .unwrap_or(0);
+        let monitors = infos
+            .iter()
+            .enumerate()
+            .map(|(idx, info)| {
+                let rect = logical_rect(info);
+                // Wayland doesn't tell clients about panels, so the whole monitor is the work area
+                Monitor::new(idx == primary, rect, rect)
+                    .with_name(output_name(info))
+                    .with_outputs(info.name.iter().cloned().collect())
+                    .with_refresh_rate(
+                        info.modes
+                            .iter()
+                            .find(|mode| mode.current)
+                            .and_then(|mode| u32::try_from(mode.refresh_rate).ok()),
+                    )
+                    .with_physical_size(Some(Size::new(
+                        f64::from(info.physical_size.0),
+                        f64::from(info.physical_size.1),
+                    )))
+                    .with_scale(Scale::new(
+                        f64::from(info.scale_factor),
+                        f64::from(info.scale_factor),
+                    ))
+            })
+            .collect::<Vec<_>>();
+        let mut current = self.monitors.write().unwrap();
+        if *current == monitors {
+            return;
+        }
+        *current = monitors;
+        drop(current);
+        if let Some(handler) = self.handler.as_mut() {
+            handler.monitors_changed();
+        }
+    }
+
+    /// The output which `monitor` describes, if it still exists
+    pub(super) fn output_of(&self, monitor: &Monitor) -> Option<WlOutput> {
+        self.output_state.outputs().find(|output| {
+            self.output_state
+                .info(output)
+                .is_some_and(|info| logical_rect(&info) == monitor.virtual_rect())
+        })
+    }
+}
+
+/// The name of the connector of an output (`DP-1`), or its model if the compositor is too
+/// old to tell us
+fn output_name(info: &OutputInfo) -> Option<String> {
+    info.name
+        .clone()
+        .or_else(|| Some(info.model.clone()).filter(|model| !model.is_empty()))
+}
+
+/// The area covered by an output in the compositor's logical coordinates, which are
+/// the same as our display points
+fn logical_rect(info: &OutputInfo) -> Rect {
+    let (x, y) = info.logical_position.unwrap_or(info.location);
+    let (width, height) = info.logical_size.unwrap_or_else(|| {
+        // Without xdg-output, we need to work out the logical size from the current mode
+        let (width, height) = info
+            .modes
+            .iter()
+            .find(|mode| mode.current)
+            .map_or((0, 0), |mode| mode.dimensions);
+        let (width, height) = match info.transform {
+            Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+                (height, width)
+            }
+            _ => (width, height),
+        };
+        let scale = info.scale_factor.max(1);
+        (width / scale, height / scale)
+    });
+    Rect::from_origin_size(
+        (f64::from(x), f64::from(y)),
+        (f64::from(width), f64::from(height)),
+    )
 }
 
 delegate_output!(WaylandState);
@@ -48,11 +137,15 @@ impl OutputHandler for WaylandState {
     }
 
     fn new_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {
-        // TODO: Tell the app about these?
+        self.update_monitors(None);
     }
 
-    fn update_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {}
+    fn update_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {
+        self.update_monitors(None);
+    }
 
-    fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {
+    fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, output: WlOutput) {
+        // The output is only removed from the `OutputState` after this is called
+        self.update_monitors(Some(&output));
     }
 }
@@ -17,24 +17,51 @@ use std::os::raw::c_void;
 use std::ptr::{null_mut, NonNull};
 use std::sync::mpsc::{self, Sender};
 use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
 
 use flo_binding::{bind, Bound, MutableBound};
 use raw_window_handle::{
     DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, RawDisplayHandle,
     RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
 };
-use smithay_client_toolkit::compositor::CompositorHandler;
+use smithay_client_toolkit::compositor::{CompositorHandler, SurfaceData};
+use smithay_client_toolkit::error::GlobalError;
 use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
 use smithay_client_toolkit::reexports::calloop::{channel, LoopHandle};
 use smithay_client_toolkit::reexports::client::protocol::wl_compositor::WlCompositor;
+use smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat;
+use smithay_client_toolkit::reexports::client::protocol::wl_shm::WlShm;
 use smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface;
-use smithay_client_toolkit::reexports::client::{protocol, Connection, Proxy, QueueHandle};
+use smithay_client_toolkit::reexports::client::{
+    protocol, Connection, Dispatch, Proxy, QueueHandle,
+};
+use smithay_client_toolkit::reexports::csd_frame::{
+    DecorationsFrame, FrameAction, FrameClick, ResizeEdge as FrameResizeEdge,
+};
+use smithay_client_toolkit::reexports::protocols::wp::fractional_scale::v1::client::{
+    wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+    wp_fractional_scale_v1::{self, WpFractionalScaleV1},
+};
+use smithay_client_toolkit::reexports::protocols::wp::viewporter::client::{
+    wp_viewport::WpViewport, wp_viewporter::WpViewporter,
+};
+use smithay_client_toolkit::reexports::protocols::xdg::shell::client::xdg_positioner::{
+    Anchor, ConstraintAdjustment, Gravity,
+};
+use smithay_client_toolkit::reexports::protocols::xdg::shell::client::{xdg_surface, xdg_toplevel};
+use smithay_client_toolkit::seat::pointer::CursorIcon;
+use smithay_client_toolkit::shell::xdg::fallback_frame::FallbackFrame;
+use smithay_client_toolkit::shell::xdg::popup::{Popup, PopupConfigure, PopupHandler};
 use smithay_client_toolkit::shell::xdg::window::{
     DecorationMode, Window, WindowConfigure, WindowDecorations, WindowHandler,
 };
-use smithay_client_toolkit::shell::xdg::XdgShell;
+use smithay_client_toolkit::shell::xdg::{XdgPositioner, XdgShell, XdgSurface};
 use smithay_client_toolkit::shell::WaylandSurface;
-use smithay_client_toolkit::{delegate_compositor, delegate_xdg_shell, delegate_xdg_window};
+use smithay_client_toolkit::shm::Shm;
+use smithay_client_toolkit::subcompositor::SubcompositorState;
+use smithay_client_toolkit::{
+    delegate_compositor, delegate_xdg_popup, delegate_xdg_shell, delegate_xdg_window,
+};
 use tracing;
 use wayland_backend::client::ObjectId;
 
@@ -45,15 +72,15 @@ use super::input::{
 use super::menu::Menu;
 use super::{ActiveAction, IdleAction, WaylandState};
 
-use crate::{backend, IdleToken, Region, Scalable};
+use crate::{backend, IdleToken, Monitor, Region, Scalable, ScaledArea};
 use crate::{
     dialog::FileDialogOptions,
     error::Error as ShellError,
-    kurbo::{Insets, Point, Rect, Size},
+    kurbo::{Insets, Point, Rect, Size, Vec2},
     mouse::{Cursor, CursorDesc},
     scale::Scale,
     text::Event,
-    window::{self, FileDialogToken, TimerToken, WinHandler, WindowLevel},
+    window::{self, FileDialogToken, ResizeEdge, TimerToken, WinHandler, WindowLevel},
     TextFieldToken,
 };
 
@@ -98,49 +125,90 @@ impl WindowHandle {
         props.wayland_window.commit();
     }
 
-    pub fn resizable(&self, _resizable: bool) {
-        tracing::warn!("resizable is unimplemented on wayland");
-        // TODO: If we are using fallback decorations, we should be able to disable
-        // dragging based resizing
+    pub fn resizable(&self, resizable: bool) {
+        // TODO: We can't stop the compositor's decorations from resizing the window, but we
+        // could set the minimum and maximum size to the current size
+        let props = self.properties();
+        let mut props = props.write().unwrap();
+        props.resizable = resizable;
+        if let Some(frame) = props.frame.as_mut() {
+            frame.set_resizable(resizable);
+        }
     }
 
     pub fn show_titlebar(&self, show_titlebar: bool) {
-        tracing::info!("show_titlebar is implemented on a best-effort basis on wayland");
-        // TODO: Track this into the fallback decorations when we add those
         let props = self.properties();
-        let props = props.read().unwrap();
+        let mut props = props.write().unwrap();
+        let Some(window) = props.wayland_window.toplevel() else {
+            return;
+        };
+        // If the compositor doesn't draw decorations, we draw our own (if `show_titlebar`)
         if show_titlebar {
-            props
-                .wayland_window
-                .request_decoration_mode(Some(DecorationMode::Server))
+            window.request_decoration_mode(Some(DecorationMode::Server))
         } else {
-            props
-                .wayland_window
-                .request_decoration_mode(Some(DecorationMode::Client))
+            window.request_decoration_mode(Some(DecorationMode::Client))
+        }
+        props.show_titlebar = show_titlebar;
+        if props.update_frame() {
+            drop(props);
+            // The area left for the content of the window has changed
+            self.defer(WindowAction::ResizeRequested);
         }
     }
 
-    pub fn set_position(&self, _position: Point) {
-        tracing::warn!("set_position is unimplemented on wayland");
-        // TODO: Use the KDE plasma extensions for this if available
-        // TODO: Use xdg_positioner if this is a child window
+    pub fn set_position(&self, new_position: Point) {
+        let props = self.properties();
+        let mut props = props.write().unwrap();
+        match &mut props.wayland_window {
+            WindowSurface::Popup { position, .. } => {
+                *position = new_position;
+                props.reposition_popup();
+            }
+            WindowSurface::Toplevel(_) => {
+                tracing::warn!("set_position is unimplemented on wayland");
+                // TODO: Use the KDE plasma extensions for this if available
+            }
+        }
     }
 
     pub fn get_position(&self) -> Point {
-        tracing::warn!("get_position is unimplemented on wayland");
-        Point::ZERO
+        let props = self.properties();
+        let props = props.read().unwrap();
+        match &props.wayland_window {
+            WindowSurface::Popup { position, .. } => *position,
+            WindowSurface::Toplevel(_) => {
+                tracing::warn!("get_position is unimplemented on wayland");
+                Point::ZERO
+            }
+        }
     }
 
     pub fn content_insets(&self) -> Insets {
-        // I *think* wayland surfaces don't care about content insets
-        // That is, all decorations (to confirm: even client side?) are 'outsets'
-        Insets::from(0.)
+        // Server side decorations are 'outsets', so only our own decorations are counted
+        let props = self.properties();
+        let props = props.read().unwrap();
+        let Some(frame) = props.frame.as_ref().filter(|frame| !frame.is_hidden()) else {
+            return Insets::ZERO;
+        };
+        let (x, y) = frame.location();
+        let (width, height) = frame.add_borders(0, 0);
+        Insets::new(
+            f64::from(-x),
+            f64::from(-y),
+            f64::from(width as i32 + x),
+            f64::from(height as i32 + y),
+        )
     }
 
     pub fn set_size(&self, size: Size) {
         let props = self.properties();
         let mut props = props.write().unwrap();
         props.requested_size = Some(size);
+        if props.wayland_window.toplevel().is_none() {
+            // The compositor decides the size of popups, so we need to ask it
+            props.reposition_popup();
+            return;
+        }
 
         // We don't need to tell the server about changing the size - so long as the size of the surface gets changed properly
         // So, all we need to do is to tell the handler about this change (after caching it here)
@@ -156,23 +224,66 @@ impl WindowHandle {
 
     pub fn set_window_state(&mut self, state: window::WindowState) {
         let props = self.properties();
-        let props = props.read().unwrap();
+        let mut props = props.write().unwrap();
+        let Some(window) = props.wayland_window.toplevel() else {
+            tracing::warn!("set_window_state is not supported for popups");
+            return;
+        };
         match state {
-            crate::WindowState::Maximized => props.wayland_window.set_maximized(),
-            crate::WindowState::Minimized => props.wayland_window.set_minimized(),
-            // TODO: I don't think we can do much better than this - we can't unset being minimised
-            crate::WindowState::Restored => props.wayland_window.unset_maximized(),
+            window::WindowState::Maximized => window.set_maximized(),
+            window::WindowState::Minimized => {
+                window.set_minimized();
+                // The compositor doesn't tell us that we are minimised, so we have to remember
+                props.window_state = window::WindowState::Minimized;
+            }
+            // We can't unset being minimised, only the user can restore the window
+            window::WindowState::Restored => {
+                window.unset_maximized();
+                window.unset_fullscreen();
+            }
+            window::WindowState::Fullscreen => {
+                drop(props);
+                self.set_fullscreen(None);
+            }
+        }
+    }
+
+    pub fn set_fullscreen(&mut self, monitor: Option<&Monitor>) {
+        if self
+            .properties()
+            .read()
+            .unwrap()
+            .wayland_window
+            .toplevel()
+            .is_none()
+        {
+            tracing::warn!("set_fullscreen is not supported for popups");
+            return;
         }
+        // Finding the output of the monitor needs the outputs we know about
+        self.defer(WindowAction::Fullscreen(monitor.cloned()));
     }
 
+    /// The state of the window, as far as we know.
+    ///
+    /// Wayland has no way to tell whether a window is minimised, so once we have asked to be
+    /// minimised we assume that we stay that way until the compositor activates the window
+    /// again, which it does when the user brings it back. This is wrong if the compositor
+    /// ignores the request, or restores the window without focusing it.
     pub fn get_window_state(&self) -> window::WindowState {
-        // We can know if we're maximised or restored, but not if minimised
-        tracing::warn!("get_window_state is unimplemented on wayland");
-        window::WindowState::Maximized
+        let props = self.properties();
+        let props = props.read().unwrap();
+        props.window_state
+    }
+
+    pub fn handle_titlebar(&self, val: bool) {
+        if val {
+            self.defer(WindowAction::Move);
+        }
     }
 
-    pub fn handle_titlebar(&self, _val: bool) {
-        tracing::warn!("handle_titlebar is unimplemented on wayland");
+    pub fn begin_resize(&self, edge: ResizeEdge) {
+        self.defer(WindowAction::Resize(edge));
     }
 
     /// Close the window.
@@ -276,13 +387,28 @@ impl WindowHandle {
         token
     }
 
-    pub fn set_cursor(&mut self, _cursor: &Cursor) {
-        tracing::warn!("unimplemented set_cursor called")
+    pub fn set_cursor(&mut self, cursor: &Cursor) {
+        {
+            let props = self.properties();
+            let mut props = props.write().unwrap();
+            // Toolkits set the cursor whenever the pointer moves, which would otherwise make
+            // us set the cursor of every seat each time
+            if props.requested_cursor == *cursor {
+                return;
+            }
+            props.requested_cursor = cursor.clone();
+        }
+        // The cursor is set per-seat, so it has to be applied from the event loop
+        self.defer(WindowAction::SetCursor(cursor.clone()));
     }
 
-    pub fn make_cursor(&self, _desc: &CursorDesc) -> Option<Cursor> {
-        tracing::warn!("unimplemented make_cursor initiated");
-        None
+    pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
+        let props = self.properties();
+        let props = props.read().unwrap();
+        let cursor = CustomCursor::new(&props.shm, &props.wayland_queue, desc)?;
+        Some(Cursor::Custom(backend::window::CustomCursor::Wayland(
+            cursor,
+        )))
     }
 
     pub fn open_file(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
@@ -314,14 +440,18 @@ impl WindowHandle {
         tracing::warn!("set_menu not implement for wayland");
     }
 
-    pub fn show_context_menu(&self, _menu: Menu, _pos: Point) {
-        tracing::warn!("show_context_menu not implement for wayland");
+    pub fn show_context_menu(&self, menu: Menu, pos: Point) {
+        // The menu grabs the seat's input, so it has to be opened from the event loop
+        self.defer(WindowAction::ContextMenu(menu, pos));
     }
 
     pub fn set_title(&self, title: &str) {
         let props: Arc<RwLock<WindowProperties>> = self.properties();
         let props = props.read().unwrap();
-        props.wayland_window.set_title(title)
+        // Popups don't have titles
+        if let Some(window) = props.wayland_window.toplevel() {
+            window.set_title(title)
+        }
     }
 }
 
@@ -452,8 +582,7 @@ impl IdleHandle {
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
-pub struct CustomCursor;
+pub use super::cursor::CustomCursor;
 
 /// Builder abstraction for creating new windows
 pub(crate) struct WindowBuilder {
@@ -463,12 +592,18 @@ pub(crate) struct WindowBuilder {
     position: Option<Point>,
     level: WindowLevel,
     state: Option<window::WindowState>,
+    /// The monitor to start fullscreen on, if the state is fullscreen
+    fullscreen_monitor: Option<Monitor>,
     // pre-scaled
     size: Option<Size>,
     min_size: Option<Size>,
     resizable: bool,
     show_titlebar: bool,
     compositor: WlCompositor,
+    shm: WlShm,
+    subcompositor: Option<Arc<SubcompositorState>>,
+    fractional_scale_manager: Option<WpFractionalScaleManagerV1>,
+    viewporter: Option<WpViewporter>,
     wayland_queue: QueueHandle<WaylandState>,
     loop_handle: LoopHandle<'static, WaylandState>,
     xdg_state: Weak<XdgShell>,
@@ -487,10 +622,15 @@ impl WindowBuilder {
             position: None,
             level: WindowLevel::AppWindow,
             state: None,
+            fullscreen_monitor: None,
             min_size: None,
             resizable: true,
             show_titlebar: true,
             compositor: app.compositor,
+            shm: app.shm,
+            subcompositor: app.subcompositor,
+            fractional_scale_manager: app.fractional_scale_manager,
+            viewporter: app.viewporter,
             wayland_queue: app.wayland_queue,
             loop_handle: app.loop_handle,
             xdg_state: app.xdg_shell,
@@ -547,6 +687,12 @@ impl WindowBuilder {
         self
     }
 
+    pub fn fullscreen(mut self, monitor: Option<&Monitor>) -> Self {
+        self.state = Some(window::WindowState::Fullscreen);
+        self.fullscreen_monitor = monitor.cloned();
+        self
+    }
+
     pub fn title(mut self, title: impl Into<String>) -> Self {
         self.title = title.into();
         self
@@ -560,32 +706,98 @@ impl WindowBuilder {
     pub fn build(self) -> Result<WindowHandle, ShellError> {
         let surface = self
             .compositor
-            .create_surface(&self.wayland_queue, Default::default());
+            .create_surface(&self.wayland_queue, SurfaceData::default());
         let xdg_shell = self
             .xdg_state
             .upgrade()
             .expect("Can only build whilst event loop hasn't ended");
-        let wayland_window = xdg_shell.create_window(
-            surface,
-            // Request server decorations, because we don't yet do client decorations properly
-            WindowDecorations::RequestServer,
-            &self.wayland_queue,
-        );
-        wayland_window.set_title(self.title);
-        // TODO: Pass this down
-        wayland_window.set_app_id("org.linebender.glazier.user_app");
-        // TODO: Convert properly, set all properties
-        // wayland_window.set_min_size(self.min_size);
+        // This is just used as the default sizes, as we don't call `size` until the requested size is used
+        let default_size = Size::new(600., 800.);
+        let wayland_window = match &self.level {
+            WindowLevel::Tooltip(parent) | WindowLevel::DropDown(parent) => {
+                let parent = parent.0.unwrap_wayland().properties();
+                let parent_props = parent.read().unwrap();
+                let position = self.position.unwrap_or_default();
+                let positioner = popup_positioner(
+                    &xdg_shell,
+                    position,
+                    parent_props.content_offset(),
+                    self.size.unwrap_or(default_size),
+                )
+                .map_err(|err| ShellError::Other(Arc::new(err.into())))?;
+                let popup = Popup::from_surface(
+                    Some(parent_props.wayland_window.xdg_surface()),
+                    &positioner,
+                    &self.wayland_queue,
+                    surface,
+                    &*xdg_shell,
+                )
+                .map_err(|err| ShellError::Other(Arc::new(err.into())))?;
+                WindowSurface::Popup {
+                    popup,
+                    position,
+                    parent: Arc::downgrade(&parent),
+                }
+            }
+            WindowLevel::AppWindow | WindowLevel::Modal(_) => {
+                // If the compositor doesn't draw decorations, we draw our own
+                let decorations = if self.show_titlebar {
+                    WindowDecorations::RequestServer
+                } else {
+                    WindowDecorations::RequestClient
+                };
+                let window = xdg_shell.create_window(surface, decorations, &self.wayland_queue);
+                window.set_title(self.title);
+                // TODO: Pass this down
+                window.set_app_id("org.linebender.glazier.user_app");
+                match &self.state {
+                    Some(window::WindowState::Maximized) => window.set_maximized(),
+                    Some(window::WindowState::Minimized) => window.set_minimized(),
+                    // This is handled once the window exists, as it needs the outputs
+                    Some(window::WindowState::Fullscreen)
+                    | Some(window::WindowState::Restored)
+                    | None => {}
+                }
+                // TODO: Convert properly, set all properties
+                // window.set_min_size(self.min_size);
+                WindowSurface::Toplevel(window)
+            }
+        };
         let window_id = WindowId::new(&wayland_window);
+        // A fractional scale can only be used if we can scale our buffers to the surface size
+        let (fractional_scale, viewport) = match (self.fractional_scale_manager, self.viewporter) {
+            (Some(manager), Some(viewporter)) => {
+                let surface = wayland_window.wl_surface();
+                (
+                    Some(manager.get_fractional_scale(
+                        surface,
+                        &self.wayland_queue,
+                        FractionalScaleData(window_id.clone()),
+                    )),
+                    Some(viewporter.get_viewport(surface, &self.wayland_queue, ViewporterData)),
+                )
+            }
+            _ => (None, None),
+        };
         let properties = WindowProperties {
             configure: None,
             requested_size: self.size,
-            // This is just used as the default sizes, as we don't call `size` until the requested size is used
-            current_size: Size::new(600., 800.),
+            current_size: default_size,
             current_scale: Scale::new(1., 1.), // TODO: NaN? - these values should (must?) not be used
             wayland_window,
             wayland_queue: self.wayland_queue,
             loop_handle: self.loop_handle,
+            xdg_shell: self.xdg_state,
+            shm: self.shm,
+            subcompositor: self.subcompositor,
+            frame: None,
+            show_titlebar: self.show_titlebar,
+            resizable: self.resizable,
+            // The compositor won't tell us that we start minimised, so remember what we asked for
+            window_state: self.state.unwrap_or(window::WindowState::Restored),
+            requested_cursor: Cursor::Arrow,
+            fractional_scale,
+            viewport,
             will_repaint: false,
             pending_frame_callback: false,
             configured: false,
@@ -608,21 +820,124 @@ impl WindowBuilder {
         // TODO: When should Window::commit be called? This feels fragile
         self.loop_sender
             .send(ActiveAction::Window(
-                window_id,
+                window_id.clone(),
                 WindowAction::Create(WaylandWindowState {
                     handler: self.handler.unwrap(),
                     properties,
                     text_input_seat: None,
                     text,
                     handle: Some(handle.clone()),
+                    cursor: Cursor::Arrow,
                 }),
             ))
             .expect("Event loop should still be valid");
+        if matches!(self.level, WindowLevel::DropDown(_)) {
+            // The grab makes the compositor dismiss the dropdown when the user clicks elsewhere
+            self.loop_sender
+                .send(ActiveAction::Window(
+                    window_id.clone(),
+                    WindowAction::GrabPopup,
+                ))
+                .expect("Event loop should still be valid");
+        }
+        if self.state == Some(window::WindowState::Fullscreen) {
+            self.loop_sender
+                .send(ActiveAction::Window(
+                    window_id,
+                    WindowAction::Fullscreen(self.fullscreen_monitor),
+                ))
+                .expect("Event loop should still be valid");
+        }
 
         Ok(handle)
     }
 }
 
+/// The edge to resize from in `xdg_toplevel`
+fn resize_edge(edge: ResizeEdge) -> xdg_toplevel::ResizeEdge {
+    match edge {
+        ResizeEdge::Top => xdg_toplevel::ResizeEdge::Top,
+        ResizeEdge::Bottom => xdg_toplevel::ResizeEdge::Bottom,
+        ResizeEdge::Left => xdg_toplevel::ResizeEdge::Left,
+        ResizeEdge::Right => xdg_toplevel::ResizeEdge::Right,
+        ResizeEdge::TopLeft => xdg_toplevel::ResizeEdge::TopLeft,
+        ResizeEdge::TopRight => xdg_toplevel::ResizeEdge::TopRight,
+        ResizeEdge::BottomLeft => xdg_toplevel::ResizeEdge::BottomLeft,
+        ResizeEdge::BottomRight => xdg_toplevel::ResizeEdge::BottomRight,
+    }
+}
+
+/// Convert the edge of a window from our decorations into our edge, if it is an edge
+fn frame_resize_edge(edge: FrameResizeEdge) -> Option<ResizeEdge> {
+    Some(match edge {
+        FrameResizeEdge::Top => ResizeEdge::Top,
+        FrameResizeEdge::Bottom => ResizeEdge::Bottom,
+        FrameResizeEdge::Left => ResizeEdge::Left,
+        FrameResizeEdge::Right => ResizeEdge::Right,
+        FrameResizeEdge::TopLeft => ResizeEdge::TopLeft,
+        FrameResizeEdge::TopRight => ResizeEdge::TopRight,
+        FrameResizeEdge::BottomLeft => ResizeEdge::BottomLeft,
+        FrameResizeEdge::BottomRight => ResizeEdge::BottomRight,
+        _ => return None,
+    })
+}
+
+/// Start an interactive move of the window (or resize from `edge`), following the pointer
+/// button which is currently held
+fn begin_move_resize(state: &WaylandState, window_id: &WindowId, edge: Option<ResizeEdge>) {
+    let Some(window) = state.windows.get(window_id) else {
+        return;
+    };
+    let props = window.properties.read().unwrap();
+    let Some(toplevel) = props.wayland_window.toplevel() else {
+        tracing::warn!("only top level windows can be moved or resized");
+        return;
+    };
+    // The compositor only allows moving or resizing whilst a button is held
+    let Some((seat, serial)) = state.held_pointer_button() else {
+        return;
+    };
+    match edge {
+        Some(edge) => toplevel.resize(seat, serial, resize_edge(edge)),
+        None => toplevel.move_(seat, serial),
+    }
+}
+
+/// Create a positioner which places a popup of `size` with its top left corner at `position`
+/// relative to the content of its parent, moving it if it would otherwise go off screen.
+///
+/// `parent_offset` is the [`content_offset`](WindowProperties::content_offset) of the parent.
+pub(super) fn popup_positioner(
+    xdg_shell: &XdgShell,
+    position: Point,
+    parent_offset: Vec2,
+    size: Size,
+) -> Result<XdgPositioner, GlobalError> {
+    let positioner = XdgPositioner::new(xdg_shell)?;
+    let size = size.ceil();
+    positioner.set_size((size.width as i32).max(1), (size.height as i32).max(1));
+    let (x, y) = anchor_point(position, parent_offset);
+    positioner.set_anchor_rect(x, y, 1, 1);
+    positioner.set_anchor(Anchor::TopLeft);
+    positioner.set_gravity(Gravity::BottomRight);
+    positioner.set_constraint_adjustment(
+        ConstraintAdjustment::SlideX | ConstraintAdjustment::SlideY | ConstraintAdjustment::FlipY,
+    );
+    if positioner.version() >= 3 {
+        // Keep the popup in place relative to its parent if the parent moves
+        positioner.set_reactive();
+    }
+    Ok(positioner)
+}
+
+/// The point in the window geometry of a popup's parent which `position`, relative to the
+/// parent's content, is at. The compositor positions popups relative to the window geometry,
+/// which includes the decorations we draw ourselves.
+fn anchor_point(position: Point, parent_offset: Vec2) -> (i32, i32) {
+    let anchor = (position + parent_offset).round();
+    (anchor.x as i32, anchor.y as i32)
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 // TODO: According to https://github.com/linebender/druid/pull/2033, this should not be
 // synced with the ID of the surface
@@ -635,6 +950,11 @@ impl WindowId {
     pub fn of_surface(surface: &WlSurface) -> Self {
         Self(surface.id())
     }
+    /// The window which `surface` is part of the client side decorations of, if any
+    pub fn of_decorations(surface: &WlSurface) -> Option<Self> {
+        let parent = surface.data::<SurfaceData>()?.parent_surface()?;
+        Some(Self::of_surface(parent))
+    }
 }
 
 /// The state associated with each window, stored in [`WaylandState`]
@@ -651,6 +971,45 @@ pub(super) struct WaylandWindowState {
     // Cheap to clone, but kept in an option to track whether
     // `connect` has been sent
     handle: Option<WindowHandle>,
+    /// The cursor shown when a pointer is over this window
+    cursor: Cursor,
+}
+
+/// The role of the surface of a window
+enum WindowSurface {
+    Toplevel(Window),
+    /// Used for [`WindowLevel::Tooltip`] and [`WindowLevel::DropDown`]
+    Popup {
+        popup: Popup,
+        /// The position of the popup relative to the content of its parent, in display points
+        position: Point,
+        parent: Weak<RwLock<WindowProperties>>,
+    },
+}
+
+impl WindowSurface {
+    fn toplevel(&self) -> Option<&Window> {
+        match self {
+            WindowSurface::Toplevel(window) => Some(window),
+            WindowSurface::Popup { .. } => None,
+        }
+    }
+
+    fn xdg_surface(&self) -> &xdg_surface::XdgSurface {
+        match self {
+            WindowSurface::Toplevel(window) => window.xdg_surface(),
+            WindowSurface::Popup { popup, .. } => popup.xdg_surface(),
+        }
+    }
+}
+
+impl WaylandSurface for WindowSurface {
+    fn wl_surface(&self) -> &WlSurface {
+        match self {
+            WindowSurface::Toplevel(window) => window.wl_surface(),
+            WindowSurface::Popup { popup, .. } => popup.wl_surface(),
+        }
+    }
 }
 
 struct WindowProperties {
@@ -661,14 +1020,36 @@ struct WindowProperties {
     // The dimensions of the surface we reported to the handler, and so report in get_size()
     // Wayland gives strong deference to the application on surface size
     // so, for example an application using wgpu could have the surface configured to be a different size
+    //
+    // This is always a whole number of pixels at `current_scale`, so that the handler
+    // can draw exactly the right size of buffer
     current_size: Size,
     current_scale: Scale,
-    // The underlying wayland Window
+    // The underlying wayland Window or Popup
     // The way to close this Window is to drop the handle
     // We make this the only handle, so we can definitely drop it
-    wayland_window: Window,
+    wayland_window: WindowSurface,
     wayland_queue: QueueHandle<WaylandState>,
     loop_handle: LoopHandle<'static, WaylandState>,
+    /// Used to move popups
+    xdg_shell: Weak<XdgShell>,
+    /// Used to create custom cursors and our decorations
+    shm: WlShm,
+    subcompositor: Option<Arc<SubcompositorState>>,
+    /// The decorations we draw if the compositor doesn't draw them
+    frame: Option<FallbackFrame<WaylandState>>,
+    show_titlebar: bool,
+    resizable: bool,
+    /// The state we last told the handler about. This follows the configure events, except
+    /// for being minimised, which we only know about when we ask for it
+    window_state: window::WindowState,
+    /// The cursor last passed to `set_cursor`, so that setting it again does nothing
+    requested_cursor: Cursor,
+    /// Tells us the exact scale of the window, if the compositor supports it.
+    /// If this is `None`, we use the integer scale of the outputs the window is on
+    fractional_scale: Option<WpFractionalScaleV1>,
+    /// Used to show our buffers at the logical size of the window when using a fractional scale
+    viewport: Option<WpViewport>,
 
     /// Wayland requires frame (throttling) callbacks be requested *before* running commit.
     /// However, user code controls when commit is called (generally through wgpu's
@@ -688,6 +1069,19 @@ struct WindowProperties {
 unsafe impl Send for WindowProperties {}
 unsafe impl Sync for WindowProperties {}
 
+impl Drop for WindowProperties {
+    fn drop(&mut self) {
+        // These extend the surface, so should be destroyed before it
+        self.frame = None;
+        if let Some(fractional_scale) = &self.fractional_scale {
+            fractional_scale.destroy();
+        }
+        if let Some(viewport) = &self.viewport {
+            viewport.destroy();
+        }
+    }
+}
+
 impl WindowProperties {
     /// Calculate the size that this window should be, given the current configuration
     /// Called in response to a configure event or a resize being requested
@@ -701,39 +1095,177 @@ impl WindowProperties {
         // TODO: Is this what we want?
         let configure = self.configure.as_ref().unwrap();
         let requested_size = self.requested_size.take();
+        // The sizes in the configure are in the compositor's logical coordinates, which are
+        // the same as our display points
         if let Some(requested_size) = requested_size {
-            if !configure.is_maximized() && !configure.is_resizing() {
-                let requested_size_absolute = requested_size.to_px(self.current_scale);
+            if !configure.is_maximized() && !configure.is_fullscreen() && !configure.is_resizing() {
                 if let Some((x, y)) = configure.suggested_bounds {
-                    if requested_size_absolute.width < x as f64
-                        && requested_size_absolute.height < y as f64
-                    {
-                        self.current_size = requested_size;
-                        return self.current_size;
+                    if requested_size.width < x as f64 && requested_size.height < y as f64 {
+                        return self.set_logical_size(requested_size);
                     }
                 } else {
-                    self.current_size = requested_size;
-                    return self.current_size;
+                    return self.set_logical_size(requested_size);
                 }
             }
         }
-        let current_size_absolute = self.current_size.to_dp(self.current_scale);
-        let new_width = configure
-            .new_size
+        // The configured size includes our decorations, if we draw them
+        let new_size = match (&self.frame, configure.new_size) {
+            (Some(frame), (Some(width), Some(height))) => frame.subtract_borders(width, height),
+            (_, new_size) => new_size,
+        };
+        let new_width = new_size
             .0
-            .map_or(current_size_absolute.width, |it| it.get() as f64);
-        let new_height = configure
-            .new_size
+            .map_or(self.current_size.width, |it| it.get() as f64);
+        let new_height = new_size
             .1
-            .map_or(current_size_absolute.height, |it| it.get() as f64);
-        let new_size_absolute = Size {
+            .map_or(self.current_size.height, |it| it.get() as f64);
+        self.set_logical_size(Size {
             height: new_height,
             width: new_width,
-        };
+        })
+    }
 
-        self.current_size = new_size_absolute.to_dp(self.current_scale);
+    /// Make the surface `size` in the compositor's logical coordinates, at the current scale.
+    ///
+    /// Returns the size which should be passed to [`WinHandler::size`], which is also set as
+    /// `self.current_size`.
+    fn set_logical_size(&mut self, size: Size) -> Size {
+        // Surfaces are always a whole number of logical pixels
+        let logical_size = size.round();
+        // The buffer should be the logical size at our scale, rounded to whole pixels, as
+        // described in the fractional scale protocol
+        let area = ScaledArea::from_px(
+            logical_size.to_px(self.current_scale).round(),
+            self.current_scale,
+        );
+        if let Some(viewport) = &self.viewport {
+            viewport.set_destination(
+                (logical_size.width as i32).max(1),
+                (logical_size.height as i32).max(1),
+            );
+        }
+        if let (WindowSurface::Toplevel(window), Some(frame)) =
+            (&self.wayland_window, self.frame.as_mut())
+        {
+            let width = (logical_size.width as u32).max(1);
+            let height = (logical_size.height as u32).max(1);
+            let (x, y) = if frame.is_hidden() {
+                (0, 0)
+            } else {
+                frame.resize(width.try_into().unwrap(), height.try_into().unwrap());
+                frame.location()
+            };
+            let (width, height) = frame.add_borders(width, height);
+            // The decorations are part of the window, so the compositor needs to know to
+            // include them when positioning it
+            window
+                .xdg_surface()
+                .set_window_geometry(x, y, width as i32, height as i32);
+        }
+        self.current_size = area.size_dp();
         self.current_size
     }
+
+    /// Show our decorations if the compositor wants us to draw decorations and the title bar
+    /// should be shown, creating them if needed.
+    ///
+    /// Returns whether the decorations were shown or hidden.
+    fn update_frame(&mut self) -> bool {
+        let (Some(configure), WindowSurface::Toplevel(window)) =
+            (self.configure.as_ref(), &self.wayland_window)
+        else {
+            return false;
+        };
+        let show = configure.decoration_mode == DecorationMode::Client && self.show_titlebar;
+        let shown = self.frame.as_ref().is_some_and(|frame| !frame.is_hidden());
+        if self.frame.is_none() {
+            if !show {
+                return false;
+            }
+            let Some(subcompositor) = self.subcompositor.clone() else {
+                tracing::warn!("cannot draw window decorations without wl_subcompositor");
+                return false;
+            };
+            let shm = Shm::from(self.shm.clone());
+            match FallbackFrame::new(window, &shm, subcompositor, self.wayland_queue.clone()) {
+                Ok(mut frame) => {
+                    frame.set_resizable(self.resizable);
+                    frame.set_scaling_factor(self.current_scale.x());
+                    self.frame = Some(frame);
+                }
+                Err(err) => {
+                    tracing::error!("failed to create window decorations: {err}");
+                    return false;
+                }
+            }
+        }
+        let frame = self.frame.as_mut().unwrap();
+        frame.update_state(configure.state);
+        frame.update_wm_capabilities(configure.capabilities);
+        frame.set_hidden(!show);
+        shown != show
+    }
+
+    /// Redraw our decorations if they have changed, such as when a button is hovered
+    fn redraw_frame(&mut self) {
+        let Some(frame) = self.frame.as_mut() else {
+            return;
+        };
+        if frame.is_dirty() && frame.draw() {
+            // The decorations are synchronised with the window, so are only shown once
+            // it is committed
+            self.wayland_window.wl_surface().commit();
+        }
+    }
+
+    /// Where the top left of our content is in our window geometry, which the compositor
+    /// positions our popups relative to. This is the size of our own decorations above and
+    /// to the left of the content.
+    fn content_offset(&self) -> Vec2 {
+        match (&self.wayland_window, &self.frame) {
+            (WindowSurface::Toplevel(_), Some(frame)) if !frame.is_hidden() => {
+                let (x, y) = frame.location();
+                Vec2::new(f64::from(-x), f64::from(-y))
+            }
+            _ => Vec2::ZERO,
+        }
+    }
+
+    /// Ask the compositor to move a popup to its position, with the requested size.
+    ///
+    /// The new size and position are applied when the popup is next configured.
+    fn reposition_popup(&self) {
+        let WindowSurface::Popup {
+            popup,
+            position,
+            parent,
+        } = &self.wayland_window
+        else {
+            return;
+        };
+        // `xdg_popup.reposition` was added in version 3
+        if popup.xdg_popup().version() < 3 {
+            tracing::warn!("the compositor doesn't support moving popups");
+            return;
+        }
+        let Some(xdg_shell) = self.xdg_shell.upgrade() else {
+            return;
+        };
+        let size = self.requested_size.unwrap_or(self.current_size);
+        match popup_positioner(&xdg_shell, *position, parent_offset(parent), size) {
+            // We don't track which reposition a configure is in response to, so the token is unused
+            Ok(positioner) => popup.reposition(&positioner, 0),
+            Err(err) => tracing::error!("failed to create positioner for popup: {err}"),
+        }
+    }
+}
+
+/// The [`content_offset`](WindowProperties::content_offset) of the parent of a popup, if it
+/// still exists
+fn parent_offset(parent: &Weak<RwLock<WindowProperties>>) -> Vec2 {
+    parent
+        .upgrade()
+        .map_or(Vec2::ZERO, |parent| parent.read().unwrap().content_offset())
 }
 
 /// The context do_paint is called in
@@ -762,6 +1294,12 @@ impl WaylandWindowState {
                 return;
             }
             props.will_repaint = false;
+            if let Some(frame) = props.frame.as_mut() {
+                // The handler will commit the window, which also shows the new decorations
+                if frame.is_dirty() {
+                    frame.draw();
+                }
+            }
             // If there is not a frame callback in flight, we request it here
             // This branch could be skipped e.g. on `configure`, which ignores frame throttling hints and
             // always paints eagerly, even if there is a frame callback running
@@ -778,7 +1316,7 @@ impl WaylandWindowState {
         let mut region = Region::EMPTY;
         {
             let props = self.properties.write().unwrap();
-            let size = props.current_size.to_dp(props.current_scale);
+            let size = props.current_size;
             region.add_rect(Rect {
                 x0: 0.0,
                 y0: 0.0,
@@ -789,6 +1327,135 @@ impl WaylandWindowState {
         self.handler.paint(&region);
     }
 
+    /// Finish handling a configure event, once the size has been calculated
+    fn configured(&mut self, display_size: Size) {
+        if let Some(handle) = self.handle.take() {
+            self.handler.connect(&crate::WindowHandle(
+                backend::window::WindowHandle::Wayland(handle),
+            ));
+            // The scale may have been set before we could tell the handler about it
+            let scale = self.properties.read().unwrap().current_scale;
+            self.handler.scale(scale);
+        }
+        self.handler.size(display_size);
+        self.do_paint(true, PaintContext::Configure);
+    }
+
+    /// Change the scale of the window, keeping its logical size the same
+    fn set_scale(&mut self, scale: Scale) {
+        let new_size;
+        {
+            let mut props = self.properties.write().unwrap();
+            props.current_scale = scale;
+            if let Some(frame) = props.frame.as_mut() {
+                frame.set_scaling_factor(scale.x());
+            }
+            if !props.configured {
+                // The handler will be told about the scale once the window is configured
+                return;
+            }
+            let logical_size = props.current_size;
+            new_size = props.set_logical_size(logical_size);
+            // avoid locking the properties into user code
+        }
+        self.handler.scale(scale);
+        self.handler.size(new_size);
+        // Nothing is shown at the new scale until the next buffer is committed
+        self.do_paint(true, PaintContext::Requested);
+    }
+
+    /// Tell our decorations that the pointer has moved over `surface`, which is part of them.
+    ///
+    /// Returns the cursor which should be shown, if it changed.
+    pub(super) fn frame_pointer_moved(
+        &mut self,
+        surface: &WlSurface,
+        pos: Point,
+        time: Duration,
+    ) -> Option<CursorIcon> {
+        let mut props = self.properties.write().unwrap();
+        let frame = props.frame.as_mut()?;
+        let icon = frame.click_point_moved(time, &surface.id(), pos.x, pos.y);
+        props.redraw_frame();
+        icon
+    }
+
+    /// Tell our decorations that the pointer has left them
+    pub(super) fn frame_pointer_left(&mut self) {
+        let mut props = self.properties.write().unwrap();
+        let Some(frame) = props.frame.as_mut() else {
+            return;
+        };
+        frame.click_point_left();
+        props.redraw_frame();
+    }
+
+    /// Handle a click on our decorations, which was the input event with `serial` on `seat`
+    pub(super) fn frame_click(
+        &mut self,
+        click: FrameClick,
+        pressed: bool,
+        time: Duration,
+        seat: &WlSeat,
+        serial: u32,
+    ) {
+        let action = {
+            let mut props = self.properties.write().unwrap();
+            let props = &mut *props;
+            let (Some(frame), WindowSurface::Toplevel(window)) =
+                (props.frame.as_mut(), &props.wayland_window)
+            else {
+                return;
+            };
+            let Some(action) = frame.on_click(time, click, pressed) else {
+                return;
+            };
+            match action {
+                FrameAction::Minimize => window.set_minimized(),
+                FrameAction::Maximize => window.set_maximized(),
+                FrameAction::UnMaximize => window.unset_maximized(),
+                FrameAction::Move => window.move_(seat, serial),
+                FrameAction::Resize(edge) => {
+                    if let Some(edge) = frame_resize_edge(edge) {
+                        window.resize(seat, serial, resize_edge(edge));
+                    }
+                }
+                FrameAction::ShowMenu(x, y) => window.show_window_menu(seat, serial, (x, y)),
+                // The handler decides whether to close, which we can't call whilst locked
+                FrameAction::Close => {}
+                _ => tracing::warn!(?action, "unknown window decoration action"),
+            }
+            action
+        };
+        if matches!(action, FrameAction::Close) {
+            self.handler.request_close();
+        }
+    }
+
+    pub(super) fn cursor(&self) -> &Cursor {
+        &self.cursor
+    }
+
+    /// The integer scale which buffers for this window (such as cursor images) should use
+    pub(super) fn buffer_scale(&self) -> u32 {
+        let props = self.properties.read().unwrap();
+        props.current_scale.x().ceil().max(1.) as u32
+    }
+
+    /// The [`content_offset`](WindowProperties::content_offset) of this window, for
+    /// positioning its popups
+    pub(super) fn content_offset(&self) -> Vec2 {
+        self.properties.read().unwrap().content_offset()
+    }
+
+    /// The surface of this window to use as the parent of popups, and the shell to create
+    /// them with, if the event loop is still running
+    pub(super) fn popup_parent(&self) -> Option<(xdg_surface::XdgSurface, Arc<XdgShell>)> {
+        let props = self.properties.read().unwrap();
+        let xdg_shell = props.xdg_shell.upgrade()?;
+        Some((props.wayland_window.xdg_surface().clone(), xdg_shell))
+    }
+
     pub(super) fn set_input_seat(&mut self, seat: SeatName) {
         assert!(self.text_input_seat.is_none());
         self.text_input_seat = Some(seat);
@@ -801,6 +1468,7 @@ impl WaylandWindowState {
 
 delegate_xdg_shell!(WaylandState);
 delegate_xdg_window!(WaylandState);
+delegate_xdg_popup!(WaylandState);
 
 delegate_compositor!(WaylandState);
 
@@ -810,28 +1478,25 @@ impl CompositorHandler for WaylandState {
         _: &Connection,
         _: &QueueHandle<Self>,
         surface: &protocol::wl_surface::WlSurface,
-        // TODO: Support the fractional-scaling extension instead
-        // This requires an update in client-toolkit and wayland-protocols
         new_factor: i32,
     ) {
-        let window = self.windows.get_mut(&WindowId::of_surface(surface));
-        let window = window.expect("Should only get events for real windows");
-        let factor = f64::from(new_factor);
-        let scale = Scale::new(factor, factor);
-        let new_size;
+        let window_id = WindowId::of_surface(surface);
+        let Some(window) = self.windows.get_mut(&window_id) else {
+            // The decorations we draw handle their own scale
+            return;
+        };
         {
-            let mut props = window.properties.write().unwrap();
-            // TODO: Effectively, we need to re-evaluate the size calculation
-            // That means we need to cache the WindowConfigure or (mostly) equivalent
-            let cur_size_raw: Size = props.current_size.to_px(props.current_scale);
-            new_size = cur_size_raw.to_dp(scale);
-            props.current_scale = scale;
-            props.current_size = new_size;
-            // avoid locking the properties into user code
+            let props = window.properties.read().unwrap();
+            if props.fractional_scale.is_some() {
+                // The fractional scale is more precise, so takes priority
+                return;
+            }
+            surface.set_buffer_scale(new_factor);
         }
-        window.handler.scale(scale);
-        window.handler.size(new_size);
-        // TODO: Do we repaint here?
+        let factor = f64::from(new_factor);
+        window.set_scale(Scale::new(factor, factor));
+        // Cursors drawn from the cursor theme need to match the new scale
+        self.update_cursors(&window_id);
     }
 
     fn frame(
@@ -906,21 +1571,187 @@ impl WindowHandler for WaylandState {
             tracing::warn!("Received configure event for unknown window");
             return;
         };
-        if let Some(handle) = window.handle.take() {
-            window.handler.connect(&crate::WindowHandle(
-                backend::window::WindowHandle::Wayland(handle),
-            ));
-        }
         // TODO: Actually use the suggestions from requested_size
         let display_size;
+        let new_state;
         {
             let mut props = window.properties.write().unwrap();
+            let state = if props.window_state == window::WindowState::Minimized
+                && !configure.is_activated()
+            {
+                // We stay minimised until the user brings the window back, which focuses it
+                window::WindowState::Minimized
+            } else if configure.is_fullscreen() {
+                window::WindowState::Fullscreen
+            } else if configure.is_maximized() {
+                window::WindowState::Maximized
+            } else {
+                window::WindowState::Restored
+            };
+            new_state = (props.window_state != state).then_some(state);
+            props.window_state = state;
             props.configure = Some(configure);
+            props.update_frame();
             display_size = props.calculate_size();
             props.configured = true;
         };
-        window.handler.size(display_size);
-        window.do_paint(true, PaintContext::Configure);
+        window.configured(display_size);
+        if let Some(state) = new_state {
+            window.handler.window_state_changed(state);
+        }
+    }
+}
+
+impl PopupHandler for WaylandState {
+    fn configure(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        popup: &Popup,
+        config: PopupConfigure,
+    ) {
+        if self.is_context_menu(popup.wl_surface()) {
+            self.context_menu_configured(popup.wl_surface());
+            return;
+        }
+        let Some(window) = self
+            .windows
+            .get_mut(&WindowId::of_surface(popup.wl_surface()))
+        else {
+            tracing::warn!("Received configure event for unknown popup");
+            return;
+        };
+        let display_size;
+        {
+            let mut props = window.properties.write().unwrap();
+            // The compositor has the final say on where popups go, and how big they are
+            if let WindowSurface::Popup {
+                position, parent, ..
+            } = &mut props.wayland_window
+            {
+                let geometry_position =
+                    Point::new(config.position.0.into(), config.position.1.into());
+                *position = geometry_position - parent_offset(parent);
+            }
+            props.requested_size = None;
+            display_size =
+                props.set_logical_size(Size::new(config.width.into(), config.height.into()));
+            props.configured = true;
+        }
+        window.configured(display_size);
+    }
+
+    fn done(&mut self, _: &Connection, _: &QueueHandle<Self>, popup: &Popup) {
+        if self.is_context_menu(popup.wl_surface()) {
+            // The compositor dismissed the menu, such as when the user clicked elsewhere
+            self.context_menu = None;
+            return;
+        }
+        // The compositor has already hidden the popup, so the handler can't veto this
+        self.close_window(&WindowId::of_surface(popup.wl_surface()));
+    }
+}
+
+impl WaylandState {
+    /// Stop tracking the window, which will be destroyed once the last handle to it is dropped
+    fn close_window(&mut self, window_id: &WindowId) {
+        {
+            let Some(mut win) = self.windows.remove(window_id) else {
+                tracing::error!("Tried to close the same window twice");
+                return;
+            };
+            if let Some(seat) = win.text_input_seat {
+                let seat = input_state(&mut self.input_states, seat);
+                seat.window_deleted(&mut self.windows);
+            }
+            if self
+                .context_menu
+                .as_ref()
+                .is_some_and(|menu| menu.owner() == window_id)
+            {
+                self.context_menu = None;
+            }
+            win.handler.destroy();
+        }
+        // We will drop the proper wayland window later when we Drop window.props
+        if self.windows.is_empty()
+            && self
+                .handler
+                .as_mut()
+                .map(|handler| handler.should_quit_after_last_window())
+                .unwrap_or(true)
+        {
+            self.loop_signal.stop();
+        }
+    }
+}
+
+pub(super) struct FractionalScaleManagerData;
+pub(super) struct ViewporterData;
+/// The user data of the fractional scale of a window's surface
+struct FractionalScaleData(WindowId);
+
+impl Dispatch<WpFractionalScaleManagerV1, FractionalScaleManagerData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &WpFractionalScaleManagerV1,
+        event: <WpFractionalScaleManagerV1 as Proxy>::Event,
+        _: &FractionalScaleManagerData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        tracing::error!(?event, "unexpected wp_fractional_scale_manager_v1 event");
+    }
+}
+
+impl Dispatch<WpFractionalScaleV1, FractionalScaleData> for WaylandState {
+    fn event(
+        state: &mut Self,
+        _: &WpFractionalScaleV1,
+        event: <WpFractionalScaleV1 as Proxy>::Event,
+        data: &FractionalScaleData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wp_fractional_scale_v1::Event::PreferredScale { scale } => {
+                let Some(window) = state.windows.get_mut(&data.0) else {
+                    return;
+                };
+                // The scale is sent as the numerator of a fraction with a denominator of 120
+                let factor = f64::from(scale) / 120.;
+                window.set_scale(Scale::new(factor, factor));
+                // Cursors drawn from the cursor theme need to match the new scale
+                state.update_cursors(&data.0);
+            }
+            _ => tracing::error!(?event, "unexpected wp_fractional_scale_v1 event"),
+        }
+    }
+}
+
+impl Dispatch<WpViewporter, ViewporterData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &WpViewporter,
+        event: <WpViewporter as Proxy>::Event,
+        _: &ViewporterData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        tracing::error!(?event, "unexpected wp_viewporter event");
+    }
+}
+
+impl Dispatch<WpViewport, ViewporterData> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &WpViewport,
+        event: <WpViewport as Proxy>::Event,
+        _: &ViewporterData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        tracing::error!(?event, "unexpected wp_viewport event");
     }
 }
 
@@ -934,6 +1765,17 @@ pub(super) enum WindowAction {
     Create(WaylandWindowState),
     AnimationRequested,
     TextField(TextFieldChange),
+    SetCursor(Cursor),
+    /// Grab the input for a newly created dropdown
+    GrabPopup,
+    /// Start moving the window with the held pointer button
+    Move,
+    /// Start resizing the window with the held pointer button
+    Resize(ResizeEdge),
+    /// Make the window fullscreen, on the output of the monitor if given
+    Fullscreen(Option<Monitor>),
+    /// Open a context menu at the given position in the window
+    ContextMenu(Menu, Point),
 }
 
 impl WindowAction {
@@ -953,23 +1795,7 @@ impl WindowAction {
                 // TODO: Should this only happen if the size is actually different?
                 window.do_paint(true, PaintContext::Requested);
             }
-            WindowAction::Close => {
-                // Remove the window from tracking
-                {
-                    let Some(win) = state.windows.remove(&window_id) else {
-                        tracing::error!("Tried to close the same window twice");
-                        return;
-                    };
-                    if let Some(seat) = win.text_input_seat {
-                        let seat = input_state(&mut state.input_states, seat);
-                        seat.window_deleted(&mut state.windows);
-                    }
-                }
-                // We will drop the proper wayland window later when we Drop window.props
-                if state.windows.is_empty() {
-                    state.loop_signal.stop();
-                }
-            }
+            WindowAction::Close => state.close_window(&window_id),
             WindowAction::Create(win_state) => {
                 state.windows.insert(window_id, win_state);
             }
@@ -992,6 +1818,59 @@ impl WindowAction {
                     &window_id,
                 );
             }
+            WindowAction::SetCursor(cursor) => {
+                let Some(window) = state.windows.get_mut(&window_id) else {
+                    return;
+                };
+                window.cursor = cursor;
+                state.update_cursors(&window_id);
+            }
+            WindowAction::GrabPopup => {
+                let Some(window) = state.windows.get(&window_id) else {
+                    return;
+                };
+                let props = window.properties.read().unwrap();
+                if let WindowSurface::Popup { popup, .. } = &props.wayland_window {
+                    state.grab_popup(popup.xdg_popup());
+                }
+            }
+            WindowAction::Move => begin_move_resize(state, &window_id, None),
+            WindowAction::Resize(edge) => begin_move_resize(state, &window_id, Some(edge)),
+            WindowAction::Fullscreen(monitor) => {
+                let Some(window) = state.windows.get(&window_id) else {
+                    return;
+                };
+                let props = window.properties.read().unwrap();
+                let Some(toplevel) = props.wayland_window.toplevel() else {
+                    return;
+                };
+                let output = monitor.as_ref().and_then(|monitor| {
+                    let output = state.output_of(monitor);
+                    if output.is_none() {
+                        tracing::warn!(?monitor, "cannot go fullscreen on an unknown monitor");
+                    }
+                    output
+                });
+                toplevel.set_fullscreen(output.as_ref());
+            }
+            WindowAction::ContextMenu(menu, pos) => state.show_context_menu(window_id, menu, pos),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::anchor_point;
+    use crate::kurbo::{Point, Vec2};
+    use test_log::test;
+
+    #[test]
+    fn popup_anchor_includes_decorations() {
+        // Without our own decorations, the content is the window geometry
+        assert_eq!(anchor_point(Point::new(10., 20.), Vec2::ZERO), (10, 20));
+        // A title bar above the content pushes the popup down by its height
+        let offset = Vec2::new(0., 37.);
+        assert_eq!(anchor_point(Point::new(10., 20.), offset), (10, 57));
+        assert_eq!(anchor_point(Point::new(10.4, -5.6), offset), (10, 31));
+    }
+}
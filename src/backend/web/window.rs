@@ -45,6 +45,7 @@ use crate::pointer::{
     MouseInfo, PointerButton, PointerButtons, PointerEvent, PointerId, PointerType,
 };
 use crate::region::Region;
+use crate::screen::Monitor;
 use crate::text::{simulate_input, Event};
 use crate::window;
 use crate::window::{
@@ -419,6 +420,11 @@ impl WindowBuilder {
         self
     }
 
+    pub fn fullscreen(self, _monitor: Option<&Monitor>) -> Self {
+        // Ignored
+        self
+    }
+
     pub fn level(self, _level: WindowLevel) -> Self {
         // ignored
         self
@@ -554,6 +560,10 @@ impl WindowHandle {
         warn!("WindowHandle::set_window_state unimplemented for web.");
     }
 
+    pub fn set_fullscreen(&self, _monitor: Option<&Monitor>) {
+        warn!("WindowHandle::set_fullscreen unimplemented for web.");
+    }
+
     pub fn get_window_state(&self) -> window::WindowState {
         warn!("WindowHandle::get_window_state unimplemented for web.");
         window::WindowState::Restored
@@ -563,6 +573,10 @@ impl WindowHandle {
         warn!("WindowHandle::handle_titlebar unimplemented for web.");
     }
 
+    pub fn begin_resize(&self, _edge: window::ResizeEdge) {
+        warn!("WindowHandle::begin_resize unimplemented for web.");
+    }
+
     pub fn close(&self) {
         // TODO
     }
@@ -65,6 +65,7 @@ use crate::pointer::{
 };
 use crate::region::Region;
 use crate::scale::{Scalable, Scale, ScaledArea};
+use crate::screen::Monitor;
 use crate::text::{simulate_input, Event};
 use crate::window;
 use crate::window::{
@@ -557,12 +558,18 @@ impl MyWndProc {
                     self.with_window_state(|s| s.is_resizable.set(resizable));
                     set_style(hwnd, resizable, self.has_titlebar());
                 }
+                DeferredOp::SetWindowState(window::WindowState::Fullscreen) => {
+                    warn!("Fullscreen windows are currently unimplemented for Windows.");
+                }
                 DeferredOp::SetWindowState(val) => {
                     let show = if self.handle.borrow().is_focusable() {
                         match val {
                             window::WindowState::Maximized => SW_MAXIMIZE,
                             window::WindowState::Minimized => SW_MINIMIZE,
-                            window::WindowState::Restored => SW_RESTORE,
+                            // Fullscreen is handled above
+                            window::WindowState::Restored | window::WindowState::Fullscreen => {
+                                SW_RESTORE
+                            }
                         }
                     } else {
                         SW_SHOWNOACTIVATE
@@ -1263,6 +1270,10 @@ impl WindowBuilder {
         self
     }
 
+    pub fn fullscreen(self, _monitor: Option<&Monitor>) -> Self {
+        self.window_state(window::WindowState::Fullscreen)
+    }
+
     pub fn level(mut self, level: WindowLevel) -> Self {
         self.level = Some(level);
         self
@@ -1765,6 +1776,10 @@ impl WindowHandle {
         self.defer(DeferredOp::SetWindowState(state));
     }
 
+    pub fn set_fullscreen(&self, _monitor: Option<&Monitor>) {
+        self.set_window_state(window::WindowState::Fullscreen);
+    }
+
     // Gets the window state.
     pub fn get_window_state(&self) -> window::WindowState {
         // We can not store state internally because it could be modified externally.
@@ -1798,6 +1813,10 @@ impl WindowHandle {
         }
     }
 
+    pub fn begin_resize(&self, _edge: window::ResizeEdge) {
+        warn!("WindowHandle::begin_resize is currently unimplemented for Windows.");
+    }
+
     pub fn set_menu(&self, menu: Menu) {
         let accels = menu.accels();
         let hmenu = menu.into_hmenu();
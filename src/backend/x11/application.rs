@@ -14,10 +14,11 @@
 
 //! X11 implementation of features at the application scope.
 
+use std::cell::RefCell;
 use std::collections::{HashMap, VecDeque};
 use std::convert::{TryFrom, TryInto};
 use std::os::unix::io::RawFd;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Error};
@@ -27,7 +28,8 @@ use x11rb::protocol::render::{self, ConnectionExt as _, Pictformat};
 use x11rb::protocol::xinput::ChangeReason;
 use x11rb::protocol::xkb::{EventType, MapPart, SelectEventsAux};
 use x11rb::protocol::xproto::{
-    self, ConnectionExt as _, CreateWindowAux, EventMask, Timestamp, Visualtype, WindowClass,
+    self, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, CreateWindowAux, EventMask,
+    Timestamp, Visualtype, WindowClass,
 };
 use x11rb::protocol::Event;
 use x11rb::resource_manager::{
@@ -36,12 +38,18 @@ use x11rb::resource_manager::{
 use x11rb::xcb_ffi::XCBConnection;
 
 use crate::application::AppHandler;
+use crate::scale::Scale;
+use crate::screen::Monitor;
 
 use super::clipboard::Clipboard;
+use super::menu::{ContextMenu, Menu};
 use super::pointer::{DeviceInfo, PointersState};
+use super::screen;
 use super::util;
 use super::window::Window;
+use super::xsettings::XSettings;
 use crate::backend::shared::linux;
+use crate::backend::shared::menu::MenuAction;
 use crate::backend::shared::xkb::{self};
 
 // This creates a `struct WindowAtoms` containing the specified atoms as members (along with some
@@ -80,6 +88,36 @@ use crate::backend::shared::xkb::{self};
 //
 // The type of _NET_WM_NAME
 //
+// _NET_WM_STATE
+//
+// The state of a window, such as whether it is fullscreen, which is changed by asking the
+// window manager once the window is mapped.
+//
+// https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html#idm45805407959456
+//
+// _MOTIF_WM_HINTS
+//
+// Hints from the Motif toolkit, which almost all window managers use to decide whether a
+// window should be decorated.
+//
+// _NET_FRAME_EXTENTS
+//
+// The size of the decorations the window manager draws around a window.
+//
+// https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html#idm45805407939984
+//
+// WM_CHANGE_STATE
+//
+// A message asking the window manager to iconify (minimize) a window.
+//
+// https://www.x.org/releases/X11R7.6/doc/xorg-docs/specs/ICCCM/icccm.html#changing_window_state
+//
+// _NET_WM_MOVERESIZE
+//
+// A message asking the window manager to start moving or resizing a window with the pointer.
+//
+// https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html#idm45805407960032
+//
 // CLIPBOARD
 //
 // The name of the clipboard selection; used for implementing copy&paste
@@ -102,11 +140,28 @@ x11rb::atom_manager! {
         _NET_WM_PID,
         _NET_WM_NAME,
         UTF8_STRING,
+        _NET_WM_STATE,
+        _NET_WM_STATE_FULLSCREEN,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
+        _NET_WM_STATE_HIDDEN,
+        WM_CHANGE_STATE,
+        _MOTIF_WM_HINTS,
+        _NET_FRAME_EXTENTS,
+        _NET_WM_MOVERESIZE,
         _NET_WM_WINDOW_TYPE,
         _NET_WM_WINDOW_TYPE_NORMAL,
         _NET_WM_WINDOW_TYPE_DROPDOWN_MENU,
         _NET_WM_WINDOW_TYPE_TOOLTIP,
         _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_WINDOW_TYPE_POPUP_MENU,
+        _NET_WORKAREA,
+        _NET_CURRENT_DESKTOP,
+        _NET_CLIENT_LIST,
+        _NET_WM_STRUT,
+        _NET_WM_STRUT_PARTIAL,
+        MANAGER,
+        _XSETTINGS_SETTINGS,
         CLIPBOARD,
         PRIMARY,
         TARGETS,
@@ -185,8 +240,10 @@ pub(crate) struct AppInner {
     /// The visual for windows with transparent backgrounds, if supported
     argb_visual_type: Option<Visualtype>,
 
-    /// The X11 resource database used to query dpi.
-    pub(crate) rdb: ResourceDb,
+    /// The X11 resource database used to query dpi, which is reloaded when it changes.
+    rdb: RwLock<ResourceDb>,
+    /// The settings of the XSETTINGS manager, which take priority over the resource database.
+    xsettings: Mutex<XSettings>,
     pub(crate) cursors: Cursors,
     /// The clipboard implementation
     clipboard: Clipboard,
@@ -202,16 +259,22 @@ pub(crate) struct AppInner {
     window_id: u32,
     /// The mutable `Application` state.
     state: RwLock<State>,
-    /// The read end of the "idle pipe", a pipe that allows the event loop to be woken up from
-    /// other threads.
-    idle_read: RawFd,
-    /// The write end of the "idle pipe", a pipe that allows the event loop to be woken up from
-    /// other threads.
-    idle_write: RawFd,
+    /// The "idle pipe", a pipe that allows the event loop to be woken up from other threads.
+    idle_pipe: Arc<IdlePipe>,
+    /// Callbacks from [`AppHandle::run_on_main`], which are run in the idle loop. This is `None`
+    /// once the application has quit, as the callbacks will never be run.
+    main_queue: Arc<Mutex<Option<Vec<MainCallback>>>>,
+    /// The handler passed to `Application::run`.
+    handler: HandlerCell,
     /// Support for the render extension in at least version 0.5?
     render_argb32_pictformat_cursor: Option<Pictformat>,
     /// The attached input devices, with internal mutability because X events can make them change.
     pointers: RwLock<PointersState>,
+    /// The context menu which is open, if any.
+    context_menu: Mutex<Option<ContextMenu>>,
+    /// The monitors the handler was last told about, as RandR sends several events for
+    /// each change.
+    monitors: Mutex<Vec<Monitor>>,
 }
 
 /// The mutable `Application` state.
@@ -226,6 +289,12 @@ struct State {
 unsafe impl Send for State {}
 unsafe impl Sync for State {}
 
+/// The `AppHandler`, which is only ever accessed from the main thread.
+struct HandlerCell(RefCell<Option<Box<dyn AppHandler>>>);
+
+unsafe impl Send for HandlerCell {}
+unsafe impl Sync for HandlerCell {}
+
 #[derive(Clone, Debug)]
 pub(crate) struct Cursors {
     pub default: Option<xproto::Cursor>,
@@ -237,8 +306,15 @@ pub(crate) struct Cursors {
     pub col_resize: Option<xproto::Cursor>,
 }
 
+type MainCallback = Box<dyn FnOnce(Option<&mut dyn AppHandler>) + Send>;
+
+/// A handle that can be used to run code on the main thread. Note that
+/// this handle can be cloned and sent between threads.
 #[derive(Clone)]
-pub(crate) struct AppHandle;
+pub(crate) struct AppHandle {
+    queue: Arc<Mutex<Option<Vec<MainCallback>>>>,
+    pipe: Arc<IdlePipe>,
+}
 
 impl Application {
     pub fn new() -> Result<Application, Error> {
@@ -246,7 +322,8 @@ impl Application {
         Ok(Application { inner })
     }
 
-    pub fn run(self, _handler: Option<Box<dyn AppHandler>>) {
+    pub fn run(self, handler: Option<Box<dyn AppHandler>>) {
+        *self.inner.handler.0.borrow_mut() = handler;
         if let Err(e) = self.inner.run_inner() {
             tracing::error!("{}", e);
         }
@@ -281,7 +358,10 @@ impl Application {
     }
 
     pub fn get_handle(&self) -> Option<AppHandle> {
-        None
+        Some(AppHandle {
+            queue: Arc::clone(&self.inner.main_queue),
+            pipe: Arc::clone(&self.inner.idle_pipe),
+        })
     }
 }
 
@@ -331,7 +411,7 @@ impl AppInner {
             xkb_state,
         });
 
-        let (idle_read, idle_write) = nix::unistd::pipe2(nix::fcntl::OFlag::O_NONBLOCK)?;
+        let idle_pipe = Arc::new(IdlePipe::new()?);
 
         let pictformats = connection.render_query_pict_formats()?;
         let render_create_cursor_supported = matches!(connection
@@ -396,6 +476,25 @@ impl AppInner {
             .ok_or_else(|| anyhow!("Couldn't get visual from screen"))?;
         let argb_visual_type = util::get_argb_visual_type(&connection, screen)?;
 
+        let xsettings = XSettings::new(&connection, screen_num, &atoms).unwrap_or_else(|err| {
+            tracing::warn!("failed to read the XSETTINGS: {:?}", err);
+            XSettings::default()
+        });
+
+        // Listen for monitors changing, and for panels changing the work area
+        match screen::select_monitor_events(&connection, window_id) {
+            Ok(true) => {}
+            Ok(false) => tracing::info!("RandR 1.2 isn't supported, so monitors won't change"),
+            Err(err) => tracing::warn!("failed to listen for monitor changes: {:?}", err),
+        }
+        // The root window also gets the resource database, and the announcements of new
+        // XSETTINGS managers
+        connection.change_window_attributes(
+            screen.root,
+            &ChangeWindowAttributesAux::new()
+                .event_mask(EventMask::PROPERTY_CHANGE | EventMask::STRUCTURE_NOTIFY),
+        )?;
+
         let timestamp = Binding::new(x11rb::CURRENT_TIME);
 
         let shared = Arc::new(AppShared {
@@ -410,21 +509,27 @@ impl AppInner {
         let clipboard = Clipboard::new(Arc::clone(&shared), atoms.CLIPBOARD);
         let primary = Clipboard::new(Arc::clone(&shared), atoms.PRIMARY);
 
-        Ok(Arc::new(AppInner {
+        let inner = Arc::new(AppInner {
             shared,
-            rdb,
+            rdb: RwLock::new(rdb),
+            xsettings: Mutex::new(xsettings),
             window_id,
             state,
-            idle_read,
+            idle_pipe,
             cursors,
             clipboard,
             primary,
-            idle_write,
+            main_queue: Arc::new(Mutex::new(Some(Vec::new()))),
+            handler: HandlerCell(RefCell::new(None)),
             root_visual_type,
             argb_visual_type,
             render_argb32_pictformat_cursor,
             pointers: RwLock::new(pointers),
-        }))
+            context_menu: Mutex::new(None),
+            monitors: Mutex::new(Vec::new()),
+        });
+        *inner.monitors.lock().unwrap() = screen::get_monitors(&inner);
+        Ok(inner)
     }
 
     /// Return the ARGB32 pictformat of the server, but only if RENDER's CreateCursor is supported
@@ -554,6 +659,41 @@ impl AppInner {
         &self.shared.atoms
     }
 
+    /// The scale the user has asked for with `GLAZIER_OVERRIDE_SCALE`, the XSETTINGS or the
+    /// `Xft.dpi` resource. X11 only has one DPI, so this is the same on every monitor.
+    pub(crate) fn scale(&self) -> Scale {
+        let scale_override = std::env::var("GLAZIER_OVERRIDE_SCALE")
+            .ok()
+            .map(|x| x.parse::<f64>());
+
+        let xsettings_dpi = self.xsettings.lock().unwrap().dpi();
+        let dpi = scale_override
+            .or_else(|| xsettings_dpi.map(Ok))
+            .or_else(|| {
+                self.rdb
+                    .read()
+                    .unwrap()
+                    .get_value("Xft.dpi", "")
+                    .transpose()
+            });
+        match dpi {
+            Some(Ok(dpi)) => {
+                let scale = dpi / 96.;
+                Scale::new(scale, scale)
+            }
+            None => Scale::default(),
+            Some(Err(err)) => {
+                let default = Scale::default();
+                tracing::warn!(
+                    "Unable to parse dpi: {:?}, defaulting to {:?}",
+                    err,
+                    default
+                );
+                default
+            }
+        }
+    }
+
     /// Returns `Ok(true)` if we want to exit the main loop.
     fn handle_event(&self, ev: &Event) -> Result<bool, Error> {
         if ev.server_generated() {
@@ -571,6 +711,9 @@ impl AppInner {
             };
             self.shared.timestamp.set(timestamp);
         }
+        if self.handle_menu_event(ev)? {
+            return Ok(false);
+        }
         match ev {
             // NOTE: When adding handling for any of the following events,
             //       there must be a check against self.window_id
@@ -687,12 +830,36 @@ impl AppInner {
                     .context("LEAVE_NOTIFY - failed to get window")?;
                 w.handle_leave_notify(ev)?;
             }
+            Event::ClientMessage(ev) if ev.window == self.root_window() => {
+                let mut xsettings = self.xsettings.lock().unwrap();
+                if xsettings.is_new_manager(ev, self.atoms()) {
+                    let changed = xsettings
+                        .update(self.connection(), self.atoms())
+                        .context("CLIENT_MESSAGE - failed to read the new XSETTINGS")?;
+                    drop(xsettings);
+                    if changed {
+                        self.update_scale()?;
+                    }
+                }
+            }
             Event::ClientMessage(ev) => {
                 let w = self
                     .window(ev.window)
                     .context("CLIENT_MESSAGE - failed to get window")?;
                 w.handle_client_message(ev);
             }
+            Event::DestroyNotify(ev) if self.xsettings.lock().unwrap().is_destroyed(ev) => {
+                // The manager exited, so look for the one which replaces it, if there is one
+                let changed = self
+                    .xsettings
+                    .lock()
+                    .unwrap()
+                    .update(self.connection(), self.atoms())
+                    .context("DESTROY_NOTIFY - failed to read the XSETTINGS")?;
+                if changed {
+                    self.update_scale()?;
+                }
+            }
             Event::DestroyNotify(ev) => {
                 if ev.window == self.window_id {
                     // The destruction of the Application window means that
@@ -700,25 +867,39 @@ impl AppInner {
                     return Ok(true);
                 }
 
-                let w = self
-                    .window(ev.window)
-                    .context("DESTROY_NOTIFY - failed to get window")?;
+                // We also hear about some windows which aren't ours
+                let Ok(w) = self.window(ev.window) else {
+                    return Ok(false);
+                };
                 w.handle_destroy_notify(ev);
+                self.close_context_menu(ev.window);
 
                 // Remove our reference to the Window and allow it to be dropped
                 let windows_left = self
                     .remove_window(ev.window)
                     .context("DESTROY_NOTIFY - failed to remove window")?;
-                // Check if we need to finalize a quit request
-                if windows_left == 0 && borrow!(self.state)?.quitting {
-                    self.finalize_quit();
+                if windows_left == 0 {
+                    // Check if we need to finalize a quit request
+                    if borrow!(self.state)?.quitting {
+                        self.finalize_quit();
+                    } else if self.with_handler(|handler| {
+                        handler
+                            .map(|handler| handler.should_quit_after_last_window())
+                            .unwrap_or(true)
+                    }) {
+                        // The handler might have already quit the application itself
+                        let mut state = borrow_mut!(self.state)?;
+                        if !state.quitting {
+                            state.quitting = true;
+                            drop(state);
+                            self.finalize_quit();
+                        }
+                    }
                 }
             }
             Event::ConfigureNotify(ev) => {
-                if ev.window != self.window_id {
-                    let w = self
-                        .window(ev.window)
-                        .context("CONFIGURE_NOTIFY - failed to get window")?;
+                // We also hear about some windows which aren't ours, such as the root window
+                if let Ok(w) = self.window(ev.window) {
                     w.handle_configure_notify(ev)
                         .context("CONFIGURE_NOTIFY - failed to handle")?;
                 }
@@ -746,6 +927,35 @@ impl AppInner {
                 self.primary
                     .handle_property_notify(*ev)
                     .context("PROPERTY_NOTIFY event handling for primary")?;
+                if (ev.window == self.root_window() && ev.atom == self.atoms()._NET_WORKAREA)
+                    || ev.atom == self.atoms()._NET_WM_STRUT
+                    || ev.atom == self.atoms()._NET_WM_STRUT_PARTIAL
+                {
+                    // The work area changed, or a panel moved
+                    self.update_monitors();
+                } else if ev.window == self.root_window()
+                    && ev.atom == u32::from(AtomEnum::RESOURCE_MANAGER)
+                {
+                    let rdb = new_resource_db_from_default(self.connection())
+                        .context("PROPERTY_NOTIFY - failed to reload the resource database")?;
+                    *self.rdb.write().unwrap() = rdb;
+                    self.update_scale()?;
+                } else if self.xsettings.lock().unwrap().is_change(ev, self.atoms()) {
+                    let changed = self
+                        .xsettings
+                        .lock()
+                        .unwrap()
+                        .update(self.connection(), self.atoms())
+                        .context("PROPERTY_NOTIFY - failed to read the XSETTINGS")?;
+                    if changed {
+                        self.update_scale()?;
+                    }
+                } else if ev.atom == self.atoms()._NET_WM_STATE {
+                    // We also get the property changes of windows which aren't ours
+                    if let Ok(w) = self.window(ev.window) {
+                        w.handle_net_wm_state_changed();
+                    }
+                }
             }
             Event::FocusIn(ev) => {
                 let w = self
@@ -764,6 +974,9 @@ impl AppInner {
 
                 w.handle_lost_focus(&mut state.xkb_state);
             }
+            Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_) => {
+                self.update_monitors();
+            }
             Event::Error(e) => {
                 // TODO: if an error is caused by the present extension, disable it and fall back
                 // to copying pixels. This was blocked on
@@ -817,7 +1030,7 @@ impl AppInner {
             if event.is_none() {
                 poll_with_timeout(
                     &self.shared.connection,
-                    self.idle_read,
+                    self.idle_pipe.read,
                     next_timeout,
                     next_idle_time,
                 )
@@ -854,7 +1067,8 @@ impl AppInner {
             }
             if now >= next_idle_time {
                 last_idle_time = now;
-                drain_idle_pipe(self.idle_read)?;
+                drain_idle_pipe(self.idle_pipe.read)?;
+                self.run_main_callbacks();
 
                 if let Ok(state) = self.state.read() {
                     for w in state.windows.values() {
@@ -867,27 +1081,232 @@ impl AppInner {
         }
     }
 
-    fn finalize_quit(&self) {
-        log_x11!(self.shared.connection.destroy_window(self.window_id));
-        if let Err(e) = nix::unistd::close(self.idle_read) {
-            tracing::error!("Error closing idle_read: {}", e);
+    /// Run the callbacks which were queued with [`AppHandle::run_on_main`].
+    fn run_main_callbacks(&self) {
+        // Take the queue first, so that callbacks can queue more callbacks
+        let callbacks = self
+            .main_queue
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default();
+        for callback in callbacks {
+            self.with_handler(callback);
+        }
+    }
+
+    /// The root window of our screen
+    fn root_window(&self) -> xproto::Window {
+        self.connection().setup().roots[self.screen_num()].root
+    }
+
+    /// The DPI settings changed, so tell the windows about their new scale.
+    fn update_scale(&self) -> Result<(), Error> {
+        let scale = self.scale();
+        let windows = borrow!(self.state)?
+            .windows
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        for window in windows {
+            window.handle_scale_changed(scale)?;
+        }
+        // The monitors report the scale too
+        self.update_monitors();
+        Ok(())
+    }
+
+    /// Check whether the monitors have changed, and tell the handler if they have.
+    fn update_monitors(&self) {
+        let monitors = screen::get_monitors(self);
+        let mut current = self.monitors.lock().unwrap();
+        if *current == monitors {
+            return;
+        }
+        *current = monitors;
+        drop(current);
+        self.with_handler(|handler| {
+            if let Some(handler) = handler {
+                handler.monitors_changed();
+            }
+        });
+    }
+
+    /// Call `f` with the handler passed to `Application::run`, if there is one.
+    pub(crate) fn with_handler<T>(&self, f: impl FnOnce(Option<&mut dyn AppHandler>) -> T) -> T {
+        match self.handler.0.try_borrow_mut() {
+            Ok(mut handler) => f(handler.as_mut().map(|h| &mut **h as &mut dyn AppHandler)),
+            Err(_) => {
+                tracing::error!("Application handler already borrowed");
+                f(None)
+            }
+        }
+    }
+
+    /// Show `menu` at `pos` on the root window, for the window `owner`.
+    ///
+    /// Any menu which is already open is closed first.
+    pub(crate) fn show_context_menu(&self, owner: u32, menu: Menu, pos: (i32, i32), scale: f64) {
+        let mut context_menu = self.context_menu.lock().unwrap();
+        if let Some(mut old) = context_menu.take() {
+            old.close(self);
+        }
+        match ContextMenu::open(self, owner, menu, pos, scale) {
+            Ok(menu) => *context_menu = Some(menu),
+            Err(e) => tracing::error!("Failed to show context menu: {:#}", e),
+        }
+    }
+
+    /// Close the open context menu, if it belongs to the window `owner`.
+    fn close_context_menu(&self, owner: u32) {
+        let mut context_menu = self.context_menu.lock().unwrap();
+        if context_menu.as_ref().map(ContextMenu::owner) == Some(owner) {
+            context_menu.take().unwrap().close(self);
+        }
+    }
+
+    /// Handle `ev` if it is for the open context menu, which grabs all input while it is open.
+    ///
+    /// Returns `Ok(true)` if the event was handled.
+    fn handle_menu_event(&self, ev: &Event) -> Result<bool, Error> {
+        let mut context_menu = self.context_menu.lock().unwrap();
+        let Some(menu) = context_menu.as_mut() else {
+            return Ok(false);
+        };
+        let action = match ev {
+            Event::Expose(ev) if menu.has_window(ev.window) => {
+                menu.expose(self, ev.window)?;
+                None
+            }
+            Event::MotionNotify(ev) => {
+                menu.pointer_moved(self, ev.root_x, ev.root_y)?;
+                None
+            }
+            // Scrolling doesn't do anything in menus
+            Event::ButtonPress(ev) if !(4..=7).contains(&ev.detail) => {
+                menu.button_pressed(ev.root_x, ev.root_y)
+            }
+            Event::ButtonRelease(ev) if !(4..=7).contains(&ev.detail) => {
+                menu.button_released(ev.root_x, ev.root_y)
+            }
+            Event::ButtonPress(_) | Event::ButtonRelease(_) | Event::KeyRelease(_) => None,
+            Event::KeyPress(ev) => {
+                let keycode = ev.detail as u32;
+                let mut state = borrow_mut!(self.state)?;
+                let keysym = state.xkb_state.get_one_sym(keycode);
+                let event = state.xkb_state.key_event(
+                    keycode,
+                    keysym,
+                    keyboard_types::KeyState::Down,
+                    false,
+                );
+                drop(state);
+                menu.key_down(self, &event.key)?
+            }
+            _ => return Ok(false),
+        };
+        if let Some(action) = action {
+            let mut menu = context_menu.take().unwrap();
+            // The handler might open another menu
+            drop(context_menu);
+            menu.close(self);
+            if let MenuAction::Command(id) = action {
+                self.handle_menu_command(menu.owner(), id);
+            }
         }
-        if let Err(e) = nix::unistd::close(self.idle_write) {
-            tracing::error!("Error closing idle_write: {}", e);
+        Ok(true)
+    }
+
+    /// Send the command `id` from a menu to the window `owner`, or to the `AppHandler` if
+    /// that window has gone, as happens with the application menu on macOS.
+    fn handle_menu_command(&self, owner: u32, id: u32) {
+        let window = match borrow!(self.state) {
+            Ok(state) => state.windows.get(&owner).cloned(),
+            Err(err) => {
+                tracing::error!("MENU - failed to get window: {:#}", err);
+                return;
+            }
+        };
+        match window {
+            Some(window) => window.handle_menu_command(id),
+            None => self.with_handler(|handler| {
+                if let Some(handler) = handler {
+                    handler.command(id);
+                }
+            }),
         }
     }
 
-    pub(crate) fn idle_pipe(&self) -> RawFd {
-        self.idle_write
+    fn finalize_quit(&self) {
+        log_x11!(self.shared.connection.destroy_window(self.window_id));
+        // The callbacks will never be run, so drop them now rather than when the last handle is
+        // dropped. This is outside the lock, in case dropping them uses an `AppHandle`.
+        let callbacks = self.main_queue.lock().unwrap().take();
+        drop(callbacks);
+    }
+
+    pub(super) fn idle_pipe(&self) -> Arc<IdlePipe> {
+        Arc::clone(&self.idle_pipe)
     }
 }
 
 impl AppHandle {
-    pub fn run_on_main<F>(&self, _callback: F)
+    pub fn run_on_main<F>(&self, callback: F)
     where
         F: FnOnce(Option<&mut dyn AppHandler>) + Send + 'static,
     {
-        todo!()
+        let mut queue = self.queue.lock().unwrap();
+        // Once the application has quit, the callback would never be run
+        if let Some(queue) = queue.as_mut() {
+            queue.push(Box::new(callback));
+            self.pipe.wake();
+        }
+    }
+}
+
+/// A pipe that allows the event loop to be woken up from other threads, opened with O_NONBLOCK.
+///
+/// This is shared with every handle to the event loop, and is only closed once they have all
+/// been dropped, so that a handle can never write to a closed (or reused) file descriptor.
+pub(super) struct IdlePipe {
+    read: RawFd,
+    write: RawFd,
+}
+
+impl IdlePipe {
+    fn new() -> Result<IdlePipe, Error> {
+        let (read, write) = nix::unistd::pipe2(nix::fcntl::OFlag::O_NONBLOCK)?;
+        Ok(IdlePipe { read, write })
+    }
+
+    /// Wakes up the event loop by writing to the pipe.
+    pub(super) fn wake(&self) {
+        loop {
+            match nix::unistd::write(self.write, &[0]) {
+                Err(nix::errno::Errno::EINTR) => {}
+                // The pipe is full, so the event loop is already going to wake up
+                Err(nix::errno::Errno::EAGAIN) => break,
+                Err(e) => {
+                    tracing::error!("Failed to write to idle pipe: {}", e);
+                    break;
+                }
+                Ok(_) => {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for IdlePipe {
+    fn drop(&mut self) {
+        if let Err(e) = nix::unistd::close(self.read) {
+            tracing::error!("Error closing the read end of the idle pipe: {}", e);
+        }
+        if let Err(e) = nix::unistd::close(self.write) {
+            tracing::error!("Error closing the write end of the idle pipe: {}", e);
+        }
     }
 }
 
@@ -13,43 +13,551 @@
 // limitations under the License.
 
 //! X11 menus implementation.
+//!
+//! X11 has no native menus, so we draw them ourselves into override-redirect windows,
+//! which grab the pointer and keyboard while the menu is open.
 
+use anyhow::{anyhow, Error};
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xproto::{
+    self, AtomEnum, ConnectionExt as _, CreateGCAux, CreateWindowAux, EventMask, GrabMode,
+    GrabStatus, ImageFormat, ImageOrder, PropMode, Screen, Setup, VisualClass, Visualtype,
+    WindowClass,
+};
+use x11rb::wrapper::ConnectionExt as _;
+
+use super::application::AppInner;
+use crate::backend::shared::menu::{self, MenuAction, MenuTracker};
 use crate::hotkey::HotKey;
 
-pub struct Menu;
+pub struct Menu(menu::Menu);
 
 impl Menu {
     pub fn new() -> Menu {
-        // TODO(x11/menus): implement Menu::new (currently a no-op)
-        tracing::warn!("Menu::new is currently unimplemented for X11 backend.");
-        Menu {}
+        Menu(menu::Menu::new())
     }
 
     pub fn new_for_popup() -> Menu {
-        // TODO(x11/menus): implement Menu::new_for_popup (currently a no-op)
-        tracing::warn!("Menu::new_for_popup is currently unimplemented for X11 backend.");
-        Menu {}
+        Menu(menu::Menu::new())
     }
 
-    pub fn add_dropdown(&mut self, mut _menu: Menu, _text: &str, _enabled: bool) {
-        // TODO(x11/menus): implement Menu::add_dropdown (currently a no-op)
-        tracing::warn!("Menu::add_dropdown is currently unimplemented for X11 backend.");
+    pub fn add_dropdown(&mut self, menu: Menu, text: &str, enabled: bool) {
+        self.0.add_dropdown(menu.0, text, enabled);
     }
 
     pub fn add_item(
         &mut self,
-        _id: u32,
-        _text: &str,
-        _key: Option<&HotKey>,
-        _selected: Option<bool>,
-        _enabled: bool,
+        id: u32,
+        text: &str,
+        key: Option<&HotKey>,
+        selected: Option<bool>,
+        enabled: bool,
     ) {
-        // TODO(x11/menus): implement Menu::add_item (currently a no-op)
-        tracing::warn!("Menu::add_item is currently unimplemented for X11 backend.");
+        self.0.add_item(id, text, key, selected, enabled);
     }
 
     pub fn add_separator(&mut self) {
-        // TODO(x11/menus): implement Menu::add_separator (currently a no-op)
-        tracing::warn!("Menu::add_separator is currently unimplemented for X11 backend.");
+        self.0.add_separator();
+    }
+}
+
+/// A context menu which is open on screen.
+pub(crate) struct ContextMenu {
+    tracker: MenuTracker,
+    /// The id of the window which opened the menu, which is sent the chosen command
+    owner: u32,
+    /// The windows showing each open menu of `tracker`
+    popups: Vec<Popup>,
+    gc: xproto::Gcontext,
+    /// How the pixels of the menus are sent to the server
+    format: PixelFormat,
+    /// The integer scale the menus are drawn at
+    scale: u32,
+}
+
+/// A window showing one of the open menus
+struct Popup {
+    id: xproto::Window,
+    /// The `MenuLevel::parent` of the menu this window shows
+    parent: Option<usize>,
+    /// The hovered item when the window was last drawn
+    hovered: Option<usize>,
+    /// The position of the window relative to the root window, in pixels
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl ContextMenu {
+    /// Show `menu` with its top left corner at `(x, y)` on the root window, and grab the
+    /// pointer and keyboard.
+    pub(crate) fn open(
+        app: &AppInner,
+        owner: u32,
+        menu: Menu,
+        (x, y): (i32, i32),
+        scale: f64,
+    ) -> Result<ContextMenu, Error> {
+        let conn = app.connection();
+        let screen = &conn.setup().roots[app.screen_num()];
+        let format = PixelFormat::new(conn.setup(), screen, &app.root_visual_type());
+        match format {
+            PixelFormat::TrueColor { .. } => tracing::debug!("drawing menus as {format:?}"),
+            PixelFormat::Bitmap { .. } => {
+                tracing::warn!("the root visual isn't true colour, so menus are black and white");
+            }
+        }
+        let gc = conn.generate_id()?;
+        // The colours of bitmaps
+        let aux = CreateGCAux::new()
+            .foreground(screen.black_pixel)
+            .background(screen.white_pixel);
+        conn.create_gc(gc, screen.root, &aux)?;
+        let mut context_menu = ContextMenu {
+            tracker: MenuTracker::new(menu.0),
+            owner,
+            popups: Vec::new(),
+            gc,
+            format,
+            scale: (scale.round() as u32).max(1),
+        };
+        context_menu.update(app, Some((x, y)))?;
+
+        let window = context_menu.popups[0].id;
+        let mask = EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION;
+        let pointer = conn
+            .grab_pointer(
+                false,
+                window,
+                mask,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                x11rb::NONE,
+                x11rb::CURRENT_TIME,
+            )?
+            .reply()?;
+        let keyboard = conn
+            .grab_keyboard(
+                false,
+                window,
+                x11rb::CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?
+            .reply()?;
+        if pointer.status != GrabStatus::SUCCESS || keyboard.status != GrabStatus::SUCCESS {
+            context_menu.close(app);
+            return Err(anyhow!(
+                "failed to grab the pointer ({:?}) or keyboard ({:?})",
+                pointer.status,
+                keyboard.status
+            ));
+        }
+        Ok(context_menu)
+    }
+
+    /// The id of the window which opened the menu
+    pub(crate) fn owner(&self) -> u32 {
+        self.owner
+    }
+
+    /// Whether `window` is one of the windows of the menu
+    pub(crate) fn has_window(&self, window: xproto::Window) -> bool {
+        self.popups.iter().any(|popup| popup.id == window)
+    }
+
+    /// Ungrab the pointer and keyboard, and destroy the windows of the menu.
+    pub(crate) fn close(&mut self, app: &AppInner) {
+        let conn = app.connection();
+        log_x11!(conn.ungrab_pointer(x11rb::CURRENT_TIME));
+        log_x11!(conn.ungrab_keyboard(x11rb::CURRENT_TIME));
+        for popup in self.popups.drain(..) {
+            log_x11!(conn.destroy_window(popup.id));
+        }
+        log_x11!(conn.free_gc(self.gc));
+    }
+
+    /// The pointer moved to `(x, y)` on the root window.
+    pub(crate) fn pointer_moved(&mut self, app: &AppInner, x: i16, y: i16) -> Result<(), Error> {
+        let (level, y) = self.level_at(x, y);
+        self.tracker.pointer_moved(level, y, self.scale);
+        self.update(app, None)
+    }
+
+    /// A button was pressed at `(x, y)` on the root window.
+    pub(crate) fn button_pressed(&mut self, x: i16, y: i16) -> Option<MenuAction> {
+        let (level, _) = self.level_at(x, y);
+        self.tracker.button_pressed(level)
+    }
+
+    /// A button was released at `(x, y)` on the root window.
+    pub(crate) fn button_released(&mut self, x: i16, y: i16) -> Option<MenuAction> {
+        let (level, y) = self.level_at(x, y);
+        self.tracker.button_released(level, y, self.scale)
+    }
+
+    pub(crate) fn key_down(
+        &mut self,
+        app: &AppInner,
+        key: &crate::KbKey,
+    ) -> Result<Option<MenuAction>, Error> {
+        let action = self.tracker.key_down(key);
+        self.update(app, None)?;
+        Ok(action)
+    }
+
+    /// Redraw `window`, which has been exposed.
+    pub(crate) fn expose(&self, app: &AppInner, window: xproto::Window) -> Result<(), Error> {
+        match self.popups.iter().position(|popup| popup.id == window) {
+            Some(level) => self.draw(app, level),
+            None => Ok(()),
+        }
+    }
+
+    /// The open menu under `(x, y)` on the root window, and the distance of the point from
+    /// the top of that menu.
+    fn level_at(&self, x: i16, y: i16) -> (Option<usize>, f64) {
+        let (x, y) = (i32::from(x), i32::from(y));
+        // Later menus are drawn above earlier ones
+        for (level, popup) in self.popups.iter().enumerate().rev() {
+            if (popup.x..popup.x + popup.width as i32).contains(&x)
+                && (popup.y..popup.y + popup.height as i32).contains(&y)
+            {
+                return (Some(level), f64::from(y - popup.y));
+            }
+        }
+        (None, 0.0)
+    }
+
+    /// Make the windows match the open menus of the tracker, and redraw any which changed.
+    ///
+    /// `origin` is the position of the root menu, which is only needed when it is first
+    /// opened.
+    fn update(&mut self, app: &AppInner, origin: Option<(i32, i32)>) -> Result<(), Error> {
+        let conn = app.connection();
+        let levels = self.tracker.levels();
+        let unchanged = self
+            .popups
+            .iter()
+            .zip(levels)
+            .take_while(|(popup, level)| popup.parent == level.parent)
+            .count();
+        for popup in self.popups.drain(unchanged..) {
+            log_x11!(conn.destroy_window(popup.id));
+        }
+
+        let screen = &conn.setup().roots[app.screen_num()];
+        let (screen_width, screen_height) = (
+            i32::from(screen.width_in_pixels),
+            i32::from(screen.height_in_pixels),
+        );
+        for (level, state) in levels.iter().enumerate().skip(self.popups.len()) {
+            let menu = self.tracker.menu(level);
+            let (width, height) = menu.size(self.scale);
+            let (x, y) = match (self.popups.last(), state.parent) {
+                (Some(parent), Some(index)) => {
+                    // Line the first item of the submenu up with the item it belongs to
+                    let parent_menu = self.tracker.menu(level - 1);
+                    let y = parent.y + parent_menu.item_top(index, self.scale) as i32
+                        - menu.item_top(0, self.scale) as i32;
+                    let right = parent.x + parent.width as i32;
+                    if right + width as i32 <= screen_width {
+                        (right, y)
+                    } else {
+                        (parent.x - width as i32, y)
+                    }
+                }
+                _ => {
+                    let (x, y) = origin.ok_or_else(|| anyhow!("the root menu was closed"))?;
+                    let y = if y + height as i32 > screen_height && y >= height as i32 {
+                        y - height as i32
+                    } else {
+                        y
+                    };
+                    (x.min(screen_width - width as i32), y)
+                }
+            };
+            let (x, y) = (x.max(0), y.min(screen_height - height as i32).max(0));
+
+            let id = conn.generate_id()?;
+            conn.create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                id,
+                screen.root,
+                x as i16,
+                y as i16,
+                width as u16,
+                height as u16,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new()
+                    .override_redirect(1)
+                    .save_under(1)
+                    .event_mask(EventMask::EXPOSURE),
+            )?;
+            let atoms = app.atoms();
+            log_x11!(conn.change_property32(
+                PropMode::REPLACE,
+                id,
+                atoms._NET_WM_WINDOW_TYPE,
+                AtomEnum::ATOM,
+                &[atoms._NET_WM_WINDOW_TYPE_POPUP_MENU],
+            ));
+            conn.map_window(id)?;
+            self.popups.push(Popup {
+                id,
+                parent: state.parent,
+                // Drawn when the window is exposed
+                hovered: state.hovered,
+                x,
+                y,
+                width,
+                height,
+            });
+        }
+
+        for level in 0..self.popups.len() {
+            let hovered = self.tracker.levels()[level].hovered;
+            if self.popups[level].hovered != hovered {
+                self.popups[level].hovered = hovered;
+                self.draw(app, level)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw the menu at `level` into its window.
+    fn draw(&self, app: &AppInner, level: usize) -> Result<(), Error> {
+        let conn = app.connection();
+        let popup = &self.popups[level];
+        let mut pixels = vec![0; (popup.width * popup.height) as usize];
+        self.tracker.draw(level, &mut pixels, self.scale);
+        let width = popup.width as usize;
+        let big_endian = conn.setup().image_byte_order == ImageOrder::MSB_FIRST;
+        let bytes = self.format.encode(&pixels, width, big_endian);
+        let (image_format, depth) = match self.format {
+            PixelFormat::TrueColor { depth, .. } => (ImageFormat::Z_PIXMAP, depth),
+            PixelFormat::Bitmap { .. } => (ImageFormat::XY_BITMAP, 1),
+        };
+
+        // Send the image in strips which fit in a request
+        let stride = self.format.stride(width);
+        let max_rows = ((conn.maximum_request_bytes() - 64) / stride).max(1);
+        for (strip, rows) in bytes.chunks(max_rows * stride).enumerate() {
+            conn.put_image(
+                image_format,
+                popup.id,
+                self.gc,
+                popup.width as u16,
+                (rows.len() / stride) as u16,
+                0,
+                (strip * max_rows) as i16,
+                0,
+                depth,
+                rows,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The layout of the pixels of the root visual, which the menu windows use
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PixelFormat {
+    /// The pixels hold their colour directly, in the bits of the masks
+    TrueColor {
+        depth: u8,
+        bits_per_pixel: u8,
+        /// Each row is padded to a multiple of this many bits
+        scanline_pad: u8,
+        red_mask: u32,
+        green_mask: u32,
+        blue_mask: u32,
+    },
+    /// The pixels are indices into a colormap, so we draw in black and white with a bitmap,
+    /// which the server draws with the colours of the graphics context
+    Bitmap {
+        /// The bits of each row are grouped into units of this many bits, which are stored
+        /// in the byte order of images
+        scanline_unit: u8,
+        scanline_pad: u8,
+        /// Whether the leftmost pixel of each unit is its least significant bit
+        lsb_first: bool,
+    },
+}
+
+impl PixelFormat {
+    fn new(setup: &Setup, screen: &Screen, visual: &Visualtype) -> PixelFormat {
+        let true_color = matches!(
+            visual.class,
+            VisualClass::TRUE_COLOR | VisualClass::DIRECT_COLOR
+        );
+        let format = setup
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == screen.root_depth);
+        match format {
+            Some(format) if true_color && matches!(format.bits_per_pixel, 8 | 16 | 24 | 32) => {
+                PixelFormat::TrueColor {
+                    depth: screen.root_depth,
+                    bits_per_pixel: format.bits_per_pixel,
+                    scanline_pad: format.scanline_pad,
+                    red_mask: visual.red_mask,
+                    green_mask: visual.green_mask,
+                    blue_mask: visual.blue_mask,
+                }
+            }
+            _ => PixelFormat::Bitmap {
+                scanline_unit: setup.bitmap_format_scanline_unit,
+                scanline_pad: setup.bitmap_format_scanline_pad,
+                lsb_first: setup.bitmap_format_bit_order == ImageOrder::LSB_FIRST,
+            },
+        }
+    }
+
+    /// The number of bytes in each row of an image `width` pixels wide
+    fn stride(&self, width: usize) -> usize {
+        let (bits, pad) = match *self {
+            PixelFormat::TrueColor {
+                bits_per_pixel,
+                scanline_pad,
+                ..
+            } => (width * usize::from(bits_per_pixel), scanline_pad),
+            PixelFormat::Bitmap { scanline_pad, .. } => (width, scanline_pad),
+        };
+        let pad = usize::from(pad.max(8));
+        bits.div_ceil(pad) * pad / 8
+    }
+
+    /// Convert `pixels`, which are `0xAARRGGBB` values in rows of `width`, into an image for
+    /// `put_image`
+    fn encode(&self, pixels: &[u32], width: usize, big_endian: bool) -> Vec<u8> {
+        let stride = self.stride(width);
+        let mut bytes = vec![0; stride * (pixels.len() / width)];
+        for (row, out) in pixels
+            .chunks_exact(width)
+            .zip(bytes.chunks_exact_mut(stride))
+        {
+            match *self {
+                PixelFormat::TrueColor {
+                    bits_per_pixel,
+                    red_mask,
+                    green_mask,
+                    blue_mask,
+                    ..
+                } => {
+                    let size = usize::from(bits_per_pixel / 8);
+                    for (&pixel, out) in row.iter().zip(out.chunks_exact_mut(size)) {
+                        let value = channel(pixel >> 16, red_mask)
+                            | channel(pixel >> 8, green_mask)
+                            | channel(pixel, blue_mask);
+                        if big_endian {
+                            out.copy_from_slice(&value.to_be_bytes()[4 - size..]);
+                        } else {
+                            out.copy_from_slice(&value.to_le_bytes()[..size]);
+                        }
+                    }
+                }
+                PixelFormat::Bitmap {
+                    scanline_unit,
+                    lsb_first,
+                    ..
+                } => {
+                    let unit = usize::from(scanline_unit.max(8));
+                    for (x, &pixel) in row.iter().enumerate() {
+                        if !is_dark(pixel) {
+                            continue;
+                        }
+                        let bit = if lsb_first {
+                            x % unit
+                        } else {
+                            unit - 1 - x % unit
+                        };
+                        let byte = if big_endian {
+                            unit / 8 - 1 - bit / 8
+                        } else {
+                            bit / 8
+                        };
+                        out[x / unit * unit / 8 + byte] |= 1 << (bit % 8);
+                    }
+                }
+            }
+        }
+        bytes
+    }
+}
+
+/// Scale the 8 bit colour channel in the low bits of `value` to the bits of `mask`
+fn channel(value: u32, mask: u32) -> u32 {
+    if mask == 0 {
+        return 0;
+    }
+    let max = (1u64 << mask.count_ones()) - 1;
+    let scaled = (u64::from(value & 0xff) * max + 127) / 255;
+    ((scaled as u32) << mask.trailing_zeros()) & mask
+}
+
+/// Whether `pixel` is drawn in the foreground colour of a bitmap, which is black
+fn is_dark(pixel: u32) -> bool {
+    let [_, r, g, b] = pixel.to_be_bytes().map(u32::from);
+    // The text and the highlighted item are dark, but the background and border are light
+    (299 * r + 587 * g + 114 * b) / 1000 < 160
+}
+
+#[cfg(test)]
+mod test {
+    use super::PixelFormat;
+    use test_log::test;
+
+    #[test]
+    fn encode_pixels() {
+        let pixels = [0xff2e3436, 0xfff6f5f4, 0xffff0000];
+        let rgb565 = PixelFormat::TrueColor {
+            depth: 16,
+            bits_per_pixel: 16,
+            scanline_pad: 32,
+            red_mask: 0xf800,
+            green_mask: 0x07e0,
+            blue_mask: 0x001f,
+        };
+        assert_eq!(
+            rgb565.encode(&pixels, 3, false),
+            [0xa7, 0x31, 0xbe, 0xf7, 0x00, 0xf8, 0, 0]
+        );
+        let rgb888 = PixelFormat::TrueColor {
+            depth: 24,
+            bits_per_pixel: 24,
+            scanline_pad: 32,
+            red_mask: 0xff0000,
+            green_mask: 0x00ff00,
+            blue_mask: 0x0000ff,
+        };
+        assert_eq!(
+            rgb888.encode(&pixels, 3, true),
+            [0x2e, 0x34, 0x36, 0xf6, 0xf5, 0xf4, 0xff, 0x00, 0x00, 0, 0, 0]
+        );
+        // Two rows, of dark, light, dark pixels
+        let pixels = [pixels, pixels].concat();
+        let bitmap = PixelFormat::Bitmap {
+            scanline_unit: 16,
+            scanline_pad: 32,
+            lsb_first: true,
+        };
+        assert_eq!(
+            bitmap.encode(&pixels, 3, false),
+            [0b101, 0, 0, 0, 0b101, 0, 0, 0]
+        );
+        let bitmap = PixelFormat::Bitmap {
+            scanline_unit: 16,
+            scanline_pad: 16,
+            lsb_first: false,
+        };
+        assert_eq!(
+            bitmap.encode(&pixels, 3, true),
+            [0b1010_0000, 0, 0b1010_0000, 0]
+        );
     }
 }
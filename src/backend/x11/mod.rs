@@ -32,6 +32,7 @@
 
 #[macro_use]
 mod util;
+mod xsettings;
 
 pub mod application;
 pub mod clipboard;
@@ -15,28 +15,33 @@
 //! X11 Monitors and Screen information.
 
 use x11rb::connection::Connection;
-use x11rb::errors::ReplyOrIdError;
-use x11rb::protocol::randr::{self, ConnectionExt as _, Crtc};
-use x11rb::protocol::xproto::{Screen, Timestamp};
+use x11rb::errors::{ReplyError, ReplyOrIdError};
+use x11rb::protocol::randr::{
+    self, ConnectionExt as _, Crtc, GetCrtcInfoReply, Mode, ModeFlag, ModeInfo, NotifyMask, Output,
+};
+use x11rb::protocol::xproto::{
+    AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, EventMask, GetPropertyReply, Screen,
+    Timestamp, Window,
+};
 
-use crate::kurbo::Rect;
+use crate::kurbo::{Rect, Size};
 use crate::screen::Monitor;
 
-use super::application::Application;
+use super::application::{AppAtoms, AppInner};
 
 fn monitor<Pos>(primary: bool, (x, y): (Pos, Pos), (width, height): (u16, u16)) -> Monitor
 where
     Pos: Into<i32>,
 {
-    let rect = Rect::from_points(
+    let rect = Rect::from_origin_size(
         (x.into() as f64, y.into() as f64),
         (width as f64, height as f64),
     );
-    // TODO: Support for work_rect. It's complicated...
+    // The work area is filled in by `get_monitors`, once we know where the panels are
     Monitor::new(primary, rect, rect)
 }
 
-pub(crate) fn get_monitors(app: &Application) -> Vec<Monitor> {
+pub(crate) fn get_monitors(app: &AppInner) -> Vec<Monitor> {
     // let (conn, screen_num) = match x11rb::connect(None) {
     //     Ok(res) => res,
     //     Err(err) => {
@@ -46,13 +51,268 @@ pub(crate) fn get_monitors(app: &Application) -> Vec<Monitor> {
     // };
     let result = get_monitors_impl(app.connection(), app.screen_num());
 
-    match result {
+    let monitors = match result {
         Ok(monitors) => monitors,
         Err(err) => {
             tracing::error!("Error in Screen::get_monitors(): {:?}", err);
-            Vec::new()
+            return Vec::new();
         }
+    };
+    // X11 has a single DPI for every monitor
+    let scale = app.scale();
+    let screen = &app.connection().setup().roots[app.screen_num()];
+    match WorkArea::new(app.connection(), screen, app.atoms()) {
+        Ok(work_area) => monitors
+            .into_iter()
+            .map(|monitor| {
+                let work_rect = work_area.of(monitor.virtual_rect());
+                monitor.with_work_rect(work_rect).with_scale(scale)
+            })
+            .collect(),
+        Err(err) => {
+            tracing::warn!("failed to get the work area of the monitors: {:?}", err);
+            monitors
+                .into_iter()
+                .map(|monitor| monitor.with_scale(scale))
+                .collect()
+        }
+    }
+}
+
+/// The parts of the screen which aren't covered by panels and docks.
+///
+/// The window manager only tells us the work area of the whole screen in `_NET_WORKAREA`,
+/// which is the bounding box of the monitors without the panels at its edges. This doesn't
+/// account for panels on edges between monitors, or at the edges of monitors which don't
+/// reach the edge of the bounding box, so we also look at the space the panels reserve
+/// themselves with their struts.
+struct WorkArea {
+    /// The work area of the current desktop, if the window manager supports it
+    area: Option<Rect>,
+    struts: Vec<Strut>,
+}
+
+/// Listen for changes to the struts of `panels`, which the window manager doesn't always
+/// reflect in `_NET_WORKAREA`.
+fn watch_panels(conn: &impl Connection, panels: &[Window]) -> Result<(), ReplyError> {
+    let setup = conn.setup();
+    let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+    let watches = panels
+        .iter()
+        // Selecting events on our own windows would replace the events they already select
+        .filter(|&&panel| panel & !setup.resource_id_mask != setup.resource_id_base)
+        .map(|&panel| conn.change_window_attributes(panel, &aux))
+        .collect::<Result<Vec<_>, _>>()?;
+    for watch in watches {
+        // The panel might have gone away since we found it, which isn't an error for us
+        match watch.check() {
+            Ok(()) | Err(ReplyError::X11Error(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// The space a panel reserves at an edge of the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Strut {
+    edge: Edge,
+    /// The reserved space, in root window coordinates
+    rect: Rect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl WorkArea {
+    fn new(conn: &impl Connection, screen: &Screen, atoms: &AppAtoms) -> Result<Self, ReplyError> {
+        let root = screen.root;
+        let property = |window: Window, property, type_: AtomEnum, length| {
+            conn.get_property(false, window, property, type_, 0, length)
+        };
+        let desktop = property(root, atoms._NET_CURRENT_DESKTOP, AtomEnum::CARDINAL, 1)?;
+        let work_areas = property(root, atoms._NET_WORKAREA, AtomEnum::CARDINAL, u32::MAX)?;
+        let clients = property(root, atoms._NET_CLIENT_LIST, AtomEnum::WINDOW, u32::MAX)?;
+        let tree = conn.query_tree(root)?;
+        // The size in the setup is out of date once the screen has been resized
+        let geometry = conn.get_geometry(root)?;
+
+        let desktop = desktop
+            .reply()?
+            .value32()
+            .and_then(|mut value| value.next())
+            .unwrap_or(0) as usize;
+        let work_areas = work_areas
+            .reply()?
+            .value32()
+            .map(|value| value.map(f64::from).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let area = match work_areas.chunks_exact(4).nth(desktop) {
+            Some(&[x, y, width, height]) => Some(Rect::new(x, y, x + width, y + height)),
+            _ => None,
+        };
+
+        // Panels aren't always in the client list, such as when the window manager
+        // doesn't manage them, so we also check the children of the root window
+        let mut windows = clients
+            .reply()?
+            .value32()
+            .map(|value| value.collect::<Vec<_>>())
+            .unwrap_or_default();
+        windows.extend(tree.reply()?.children);
+        windows.sort_unstable();
+        windows.dedup();
+
+        // Send every request before waiting for any replies, to save round-trips
+        let requests = windows
+            .iter()
+            .map(|&window| {
+                Ok((
+                    window,
+                    property(window, atoms._NET_WM_STRUT_PARTIAL, AtomEnum::CARDINAL, 12)?,
+                    property(window, atoms._NET_WM_STRUT, AtomEnum::CARDINAL, 4)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, ReplyError>>()?;
+        let geometry = geometry.reply()?;
+        let size = (f64::from(geometry.width), f64::from(geometry.height));
+        let mut struts = Vec::new();
+        let mut panels = Vec::new();
+        for (window, partial, full) in requests {
+            let partial = cardinals(partial.reply()).and_then(|value| value.try_into().ok());
+            let full = cardinals(full.reply()).and_then(|value| value.try_into().ok());
+            // The partial strut takes priority, as it is more precise
+            match (partial, full) {
+                (Some(partial), _) => struts.extend(Strut::from_partial(partial, size)),
+                (None, Some(full)) => struts.extend(Strut::from_full(full, size)),
+                (None, None) => continue,
+            }
+            panels.push(window);
+        }
+        watch_panels(conn, &panels)?;
+        Ok(WorkArea { area, struts })
+    }
+
+    /// The work area of the monitor covering `monitor`
+    fn of(&self, monitor: Rect) -> Rect {
+        // The work area of the screen doesn't cover monitors which are entirely beyond a panel
+        let area = match self.area {
+            Some(area) if monitor.intersect(area).area() > 0. => monitor.intersect(area),
+            _ => monitor,
+        };
+        let mut work = area;
+        // Struts are measured from the edge of the root window, so a panel between monitors
+        // also covers every monitor between it and the edge. It only belongs to the monitor
+        // which its inner edge is inside.
+        let inside = |value: f64, start: f64, end: f64| start < value && value < end;
+        for strut in &self.struts {
+            let rect = strut.rect;
+            let beside_x = rect.y0 < monitor.y1 && monitor.y0 < rect.y1;
+            let beside_y = rect.x0 < monitor.x1 && monitor.x0 < rect.x1;
+            match strut.edge {
+                Edge::Left if beside_x && inside(rect.x1, monitor.x0, monitor.x1) => {
+                    work.x0 = work.x0.max(rect.x1);
+                }
+                Edge::Right if beside_x && inside(rect.x0, monitor.x0, monitor.x1) => {
+                    work.x1 = work.x1.min(rect.x0);
+                }
+                Edge::Top if beside_y && inside(rect.y1, monitor.y0, monitor.y1) => {
+                    work.y0 = work.y0.max(rect.y1);
+                }
+                Edge::Bottom if beside_y && inside(rect.y0, monitor.y0, monitor.y1) => {
+                    work.y1 = work.y1.min(rect.y0);
+                }
+                _ => {}
+            }
+        }
+        // Panels covering the whole monitor are more likely to be a mistake than real
+        if work.width() <= 0. || work.height() <= 0. {
+            area
+        } else {
+            work
+        }
+    }
+}
+
+/// The values of a `CARDINAL` property, or `None` if the window doesn't have it.
+///
+/// The window might have been destroyed since we listed it, which isn't a problem.
+fn cardinals(reply: Result<GetPropertyReply, ReplyError>) -> Option<Vec<f64>> {
+    let reply = reply.ok()?;
+    let values = reply.value32()?.map(f64::from).collect();
+    Some(values)
+}
+
+impl Strut {
+    /// The struts described by a `_NET_WM_STRUT_PARTIAL` property, on a screen of `size`.
+    ///
+    /// The property holds the width of each reserved edge (left, right, top and bottom),
+    /// followed by the inclusive range each of them covers along its edge.
+    fn from_partial(values: [f64; 12], (width, height): (f64, f64)) -> Vec<Strut> {
+        let [left, right, top, bottom] = [values[0], values[1], values[2], values[3]];
+        [
+            (
+                Edge::Left,
+                left,
+                Rect::new(0., values[4], left, values[5] + 1.),
+            ),
+            (
+                Edge::Right,
+                right,
+                Rect::new(width - right, values[6], width, values[7] + 1.),
+            ),
+            (
+                Edge::Top,
+                top,
+                Rect::new(values[8], 0., values[9] + 1., top),
+            ),
+            (
+                Edge::Bottom,
+                bottom,
+                Rect::new(values[10], height - bottom, values[11] + 1., height),
+            ),
+        ]
+        .into_iter()
+        .filter(|&(_, size, _)| size > 0.)
+        .map(|(edge, _, rect)| Strut { edge, rect })
+        .collect()
+    }
+
+    /// The struts described by the older `_NET_WM_STRUT` property, which reserve the whole
+    /// of each edge
+    fn from_full([left, right, top, bottom]: [f64; 4], size: (f64, f64)) -> Vec<Strut> {
+        let (last_x, last_y) = (size.0 - 1., size.1 - 1.);
+        let ranges = [0., last_y, 0., last_y, 0., last_x, 0., last_x];
+        let mut values = [left, right, top, bottom, 0., 0., 0., 0., 0., 0., 0., 0.];
+        values[4..].copy_from_slice(&ranges);
+        Strut::from_partial(values, size)
+    }
+}
+
+/// Ask for RandR to send `window` events when the monitors change, if it is new enough to
+/// tell us about CRTCs. Returns whether it will.
+pub(crate) fn select_monitor_events(
+    conn: &impl Connection,
+    window: Window,
+) -> Result<bool, ReplyOrIdError> {
+    if conn
+        .extension_information(randr::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        return Ok(false);
     }
+    let version = conn.randr_query_version(1, 2)?.reply()?;
+    if (version.major_version, version.minor_version) < (1, 2) {
+        return Ok(false);
+    }
+    let mask = NotifyMask::SCREEN_CHANGE | NotifyMask::CRTC_CHANGE | NotifyMask::OUTPUT_CHANGE;
+    conn.randr_select_input(window, mask)?.check()?;
+    Ok(true)
 }
 
 fn get_monitors_impl(
@@ -91,24 +351,43 @@ fn get_monitors_randr_monitors(
     conn: &impl Connection,
     screen: &Screen,
 ) -> Result<Vec<Monitor>, ReplyOrIdError> {
-    let result = conn
-        .randr_get_monitors(screen.root, true)?
-        .reply()?
-        .monitors
+    let monitors = conn.randr_get_monitors(screen.root, true)?;
+    let resources = conn.randr_get_screen_resources_current(screen.root)?;
+    let monitors = monitors.reply()?.monitors;
+    let resources = resources.reply()?;
+    monitors
         .iter()
-        .map(|info| monitor(info.primary, (info.x, info.y), (info.width, info.height)))
-        .collect();
-    Ok(result)
+        .map(|info| {
+            let monitor = monitor(info.primary, (info.x, info.y), (info.width, info.height));
+            // The mode isn't part of the monitor, so is found from the CRTC of its outputs
+            describe_monitor(
+                conn,
+                monitor,
+                &info.outputs,
+                None,
+                &resources.modes,
+                resources.config_timestamp,
+            )
+        })
+        .collect()
 }
 
 fn get_monitors_randr_screen_resources_current(
     conn: &impl Connection,
     screen: &Screen,
 ) -> Result<Vec<Monitor>, ReplyOrIdError> {
-    let reply = conn
-        .randr_get_screen_resources_current(screen.root)?
-        .reply()?;
-    get_monitors_randr_crtcs_timestamp(conn, &reply.crtcs, reply.config_timestamp)
+    let reply = conn.randr_get_screen_resources_current(screen.root)?;
+    // The primary output was added in RandR 1.3
+    let primary = conn.randr_get_output_primary(screen.root)?;
+    let reply = reply.reply()?;
+    let primary = primary.reply()?.output;
+    get_monitors_randr_crtcs_timestamp(
+        conn,
+        &reply.crtcs,
+        &reply.modes,
+        reply.config_timestamp,
+        primary,
+    )
 }
 
 fn get_monitors_randr_screen_resources(
@@ -116,7 +395,13 @@ fn get_monitors_randr_screen_resources(
     screen: &Screen,
 ) -> Result<Vec<Monitor>, ReplyOrIdError> {
     let reply = conn.randr_get_screen_resources(screen.root)?.reply()?;
-    get_monitors_randr_crtcs_timestamp(conn, &reply.crtcs, reply.config_timestamp)
+    get_monitors_randr_crtcs_timestamp(
+        conn,
+        &reply.crtcs,
+        &reply.modes,
+        reply.config_timestamp,
+        x11rb::NONE,
+    )
 }
 
 // This function first sends a number of requests, collect()ing them into a Vec and then gets the
@@ -125,7 +410,9 @@ fn get_monitors_randr_screen_resources(
 fn get_monitors_randr_crtcs_timestamp(
     conn: &impl Connection,
     crtcs: &[Crtc],
+    modes: &[ModeInfo],
     config_timestamp: Timestamp,
+    primary: Output,
 ) -> Result<Vec<Monitor>, ReplyOrIdError> {
     // Request information about all CRTCs
     let requests = crtcs
@@ -133,22 +420,190 @@ fn get_monitors_randr_crtcs_timestamp(
         .map(|&crtc| conn.randr_get_crtc_info(crtc, config_timestamp))
         .collect::<Vec<_>>();
 
-    // Deal with CRTC information
-    let mut result = Vec::new();
+    // Deal with CRTC information. In clone mode, the outputs showing the same part of the
+    // screen are either on the same CRTC, or on CRTCs with the same geometry, and should
+    // be a single monitor.
+    let mut crtcs: Vec<GetCrtcInfoReply> = Vec::new();
     for request in requests.into_iter() {
         let reply = request?.reply()?;
-        if reply.width != 0 && reply.height != 0 {
-            // First CRTC is assumed to be the primary output
-            let primary = result.is_empty();
-            result.push(monitor(
-                primary,
-                (reply.x, reply.y),
-                (reply.width, reply.height),
-            ));
+        if reply.width == 0 || reply.height == 0 {
+            continue;
+        }
+        let geometry = |crtc: &GetCrtcInfoReply| (crtc.x, crtc.y, crtc.width, crtc.height);
+        match crtcs
+            .iter_mut()
+            .find(|other| geometry(other) == geometry(&reply))
+        {
+            Some(other) => other.outputs.extend(reply.outputs),
+            None => crtcs.push(reply),
         }
     }
-    // TODO: I think we need to deduplicate monitors. In clone mode, each "clone" appears as its
-    // own monitor otherwise.
 
-    Ok(result)
+    // If there is no primary output, the first CRTC is assumed to be the primary output
+    let primary = crtcs
+        .iter()
+        .position(|crtc| crtc.outputs.contains(&primary))
+        .unwrap_or(0);
+    crtcs
+        .iter()
+        .enumerate()
+        .map(|(index, crtc)| {
+            let monitor = monitor(
+                index == primary,
+                (crtc.x, crtc.y),
+                (crtc.width, crtc.height),
+            );
+            describe_monitor(
+                conn,
+                monitor,
+                &crtc.outputs,
+                Some(crtc.mode),
+                modes,
+                config_timestamp,
+            )
+        })
+        .collect()
+}
+
+/// Fill in the name, refresh rate and physical size of `monitor`, which is shown on
+/// `outputs`.
+///
+/// `mode` is the mode of the CRTC showing the monitor, or `None` to use the CRTC of its
+/// first enabled output.
+#[allow(clippy::needless_collect)]
+fn describe_monitor(
+    conn: &impl Connection,
+    monitor: Monitor,
+    outputs: &[Output],
+    mode: Option<Mode>,
+    modes: &[ModeInfo],
+    config_timestamp: Timestamp,
+) -> Result<Monitor, ReplyOrIdError> {
+    let requests = outputs
+        .iter()
+        .map(|&output| conn.randr_get_output_info(output, config_timestamp))
+        .collect::<Vec<_>>();
+    let infos = requests
+        .into_iter()
+        .map(|request| Ok(request?.reply()?))
+        .collect::<Result<Vec<_>, ReplyOrIdError>>()?;
+
+    let mode = match mode {
+        Some(mode) => Some(mode),
+        None => match infos
+            .iter()
+            .map(|info| info.crtc)
+            .find(|&crtc| crtc != x11rb::NONE)
+        {
+            Some(crtc) => Some(
+                conn.randr_get_crtc_info(crtc, config_timestamp)?
+                    .reply()?
+                    .mode,
+            ),
+            None => None,
+        },
+    };
+    let refresh_rate = mode
+        .and_then(|mode| modes.iter().find(|info| info.id == mode))
+        .and_then(refresh_rate);
+    // Mirrored outputs may disagree about their size, but the first is as good as any
+    let physical_size = infos
+        .iter()
+        .map(|info| Size::new(info.mm_width.into(), info.mm_height.into()))
+        .find(|size| size.area() > 0.);
+    // The names of the connectors, such as `HDMI-1`
+    let outputs = infos
+        .iter()
+        .map(|info| String::from_utf8_lossy(&info.name).into_owned())
+        .collect::<Vec<_>>();
+    Ok(monitor
+        .with_name(outputs.first().cloned())
+        .with_outputs(outputs)
+        .with_refresh_rate(refresh_rate)
+        .with_physical_size(physical_size))
+}
+
+/// The refresh rate of `mode` in millihertz, or `None` if its timings are unknown.
+fn refresh_rate(mode: &ModeInfo) -> Option<u32> {
+    let mut clock = u64::from(mode.dot_clock) * 1000;
+    let mut frame = u64::from(mode.htotal) * u64::from(mode.vtotal);
+    // Each line is drawn twice when double scanned, and interlaced frames only draw half
+    // of the lines
+    if mode.mode_flags.contains(ModeFlag::DOUBLE_SCAN) {
+        frame *= 2;
+    }
+    if mode.mode_flags.contains(ModeFlag::INTERLACE) {
+        clock *= 2;
+    }
+    if frame == 0 {
+        return None;
+    }
+    u32::try_from(clock / frame).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{refresh_rate, Strut, WorkArea};
+    use crate::kurbo::Rect;
+    use test_log::test;
+    use x11rb::protocol::randr::{ModeFlag, ModeInfo};
+
+    #[test]
+    fn work_area_of_monitors() {
+        let size = (3840., 1080.);
+        let left = Rect::new(0., 0., 1920., 1080.);
+        let right = Rect::new(1920., 0., 3840., 1080.);
+        // A panel along the bottom of the left monitor, and one on the left of the right
+        // monitor, which isn't at the edge of the screen, so its strut also covers the
+        // left monitor
+        let mut struts =
+            Strut::from_partial([0., 0., 0., 40., 0., 0., 0., 0., 0., 0., 0., 1919.], size);
+        struts.extend(Strut::from_partial(
+            [1968., 0., 0., 0., 0., 1079., 0., 0., 0., 0., 0., 0.],
+            size,
+        ));
+        let work_area = WorkArea {
+            area: Some(Rect::new(0., 0., 3840., 1040.)),
+            struts,
+        };
+        assert_eq!(work_area.of(left), Rect::new(0., 0., 1920., 1040.));
+        assert_eq!(work_area.of(right), Rect::new(1968., 0., 3840., 1040.));
+
+        let work_area = WorkArea {
+            area: None,
+            struts: Strut::from_full([0., 0., 30., 0.], size),
+        };
+        assert_eq!(work_area.of(left), Rect::new(0., 30., 1920., 1080.));
+        assert_eq!(work_area.of(right), Rect::new(1920., 30., 3840., 1080.));
+    }
+
+    #[test]
+    fn refresh_rate_of_modes() {
+        // The standard CEA 1920x1080 mode at 60Hz
+        let mode = ModeInfo {
+            id: 1,
+            width: 1920,
+            height: 1080,
+            dot_clock: 148_500_000,
+            hsync_start: 2008,
+            hsync_end: 2052,
+            htotal: 2200,
+            hskew: 0,
+            vsync_start: 1084,
+            vsync_end: 1089,
+            vtotal: 1125,
+            name_len: 0,
+            mode_flags: ModeFlag::HSYNC_POSITIVE | ModeFlag::VSYNC_POSITIVE,
+        };
+        assert_eq!(refresh_rate(&mode), Some(60_000));
+
+        let interlaced = ModeInfo {
+            mode_flags: ModeFlag::INTERLACE,
+            ..mode
+        };
+        assert_eq!(refresh_rate(&interlaced), Some(120_000));
+
+        let unknown = ModeInfo { vtotal: 0, ..mode };
+        assert_eq!(refresh_rate(&unknown), None);
+    }
 }
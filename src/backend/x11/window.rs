@@ -17,7 +17,6 @@
 use std::collections::BinaryHeap;
 use std::convert::TryFrom;
 use std::num::NonZero;
-use std::os::unix::io::RawFd;
 use std::panic::Location;
 use std::ptr::NonNull;
 use std::sync::{Arc, Mutex, RwLock};
@@ -34,8 +33,10 @@ use tracing::{error, warn};
 use x11rb::connection::Connection;
 use x11rb::errors::ReplyOrIdError;
 use x11rb::properties::{WmHints, WmHintsState, WmSizeHints};
-use x11rb::protocol::render::Pictformat;
-use x11rb::protocol::xinput::{self, DeviceType, ModifierInfo, TouchEventFlags};
+use x11rb::protocol::render::{ConnectionExt as _, Pictformat};
+use x11rb::protocol::xinput::{
+    self, ConnectionExt as _, DeviceType, ModifierInfo, TouchEventFlags,
+};
 use x11rb::protocol::xproto::{
     self, AtomEnum, ChangeWindowAttributesAux, ColormapAlloc, ConfigureNotifyEvent,
     ConfigureWindowAux, ConnectionExt, EventMask, ImageOrder as X11ImageOrder, KeyButMask,
@@ -58,13 +59,14 @@ use crate::kurbo::{Insets, Point, Rect, Size, Vec2};
 use crate::mouse::{Cursor, CursorDesc};
 use crate::region::Region;
 use crate::scale::Scale;
+use crate::screen::Monitor;
 use crate::text::Event;
 use crate::window::{
-    FileDialogToken, IdleToken, TextFieldToken, TimerToken, WinHandler, WindowLevel,
+    FileDialogToken, IdleToken, ResizeEdge, TextFieldToken, TimerToken, WinHandler, WindowLevel,
 };
 use crate::{window, PointerButton, PointerButtons, PointerEvent, ScaledArea};
 
-use super::application::Application;
+use super::application::{Application, IdlePipe};
 use super::dialog;
 use super::menu::Menu;
 
@@ -88,8 +90,11 @@ pub(crate) struct WindowBuilder {
     size: Size,
     min_size: Size,
     resizable: bool,
+    show_titlebar: bool,
     level: WindowLevel,
     state: Option<window::WindowState>,
+    /// The monitor to start fullscreen on, if the state is fullscreen
+    fullscreen_monitor: Option<Monitor>,
 }
 
 impl WindowBuilder {
@@ -103,8 +108,10 @@ impl WindowBuilder {
             size: Size::new(500.0, 400.0),
             min_size: Size::new(0.0, 0.0),
             resizable: true,
+            show_titlebar: true,
             level: WindowLevel::AppWindow,
             state: None,
+            fullscreen_monitor: None,
         }
     }
 
@@ -133,9 +140,8 @@ impl WindowBuilder {
         self
     }
 
-    pub fn show_titlebar(self, _show_titlebar: bool) -> Self {
-        // not sure how to do this, maybe _MOTIF_WM_HINTS?
-        warn!("WindowBuilder::show_titlebar is currently unimplemented for X11 backend.");
+    pub fn show_titlebar(mut self, show_titlebar: bool) -> Self {
+        self.show_titlebar = show_titlebar;
         self
     }
 
@@ -159,6 +165,12 @@ impl WindowBuilder {
         self
     }
 
+    pub fn fullscreen(mut self, monitor: Option<&Monitor>) -> Self {
+        self.state = Some(window::WindowState::Fullscreen);
+        self.fullscreen_monitor = monitor.cloned();
+        self
+    }
+
     pub fn title<S: Into<String>>(mut self, title: S) -> Self {
         self.title = title.into();
         self
@@ -177,26 +189,7 @@ impl WindowBuilder {
         let id = conn.generate_id()?;
         let setup = conn.setup();
 
-        let scale_override = std::env::var("GLAZIER_OVERRIDE_SCALE")
-            .ok()
-            .map(|x| x.parse::<f64>());
-
-        let scale =
-            match scale_override.or_else(|| self.app.rdb.get_value("Xft.dpi", "").transpose()) {
-                Some(Ok(dpi)) => {
-                    let scale = dpi / 96.;
-                    Scale::new(scale, scale)
-                }
-                None => Scale::default(),
-                Some(Err(err)) => {
-                    let default = Scale::default();
-                    warn!(
-                        "Unable to parse dpi: {:?}, defaulting to {:?}",
-                        err, default
-                    );
-                    default
-                }
-            };
+        let scale = self.app.scale();
 
         let size_px = self.size.to_px(scale);
         let screen = setup
@@ -222,7 +215,8 @@ impl WindowBuilder {
                 | EventMask::KEY_PRESS
                 | EventMask::KEY_RELEASE
                 | EventMask::FOCUS_CHANGE
-                | EventMask::LEAVE_WINDOW,
+                | EventMask::LEAVE_WINDOW
+                | EventMask::PROPERTY_CHANGE,
         );
         if transparent {
             let colormap = conn.generate_id()?;
@@ -359,13 +353,32 @@ impl WindowBuilder {
             .set_normal_hints(conn, id)
             .context("set wm normal hints"));
 
-        // TODO: set _NET_WM_STATE
+        // Until the window is mapped, we set its state directly instead of asking the window
+        // manager to change it
+        let net_wm_state: &[xproto::Atom] = match &self.state {
+            Some(window::WindowState::Maximized) => &[
+                atoms._NET_WM_STATE_MAXIMIZED_VERT,
+                atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+            ],
+            Some(window::WindowState::Fullscreen) => &[atoms._NET_WM_STATE_FULLSCREEN],
+            _ => &[],
+        };
+        if !net_wm_state.is_empty() {
+            log_x11!(conn.change_property32(
+                PropMode::REPLACE,
+                id,
+                atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                net_wm_state,
+            ));
+        }
         let mut hints = WmHints::new();
-        if let Some(state) = self.state {
+        if let Some(state) = &self.state {
             hints.initial_state = Some(match state {
                 window::WindowState::Maximized => WmHintsState::Normal,
                 window::WindowState::Minimized => WmHintsState::Iconic,
                 window::WindowState::Restored => WmHintsState::Normal,
+                window::WindowState::Fullscreen => WmHintsState::Normal,
             });
         }
         log_x11!(hints.set(conn, id).context("set wm hints"));
@@ -413,11 +426,21 @@ impl WindowBuilder {
             next_text_field: Binding::new(None),
             active_text_field: Binding::new(None),
             need_to_reset_compose: Binding::new(false),
+            held_button: Binding::new(None),
+            // Corrected by the first `_NET_WM_STATE` change, if the window manager disagrees
+            window_state: Binding::new(self.state.unwrap_or(window::WindowState::Restored)),
             parent,
         });
 
         window.set_title(&self.title);
-        if let Some(pos) = self.position {
+        if !self.show_titlebar {
+            window.show_titlebar(false);
+        }
+        if let (Some(window::WindowState::Fullscreen), Some(monitor)) =
+            (self.state, &self.fullscreen_monitor)
+        {
+            window.move_to_monitor(monitor);
+        } else if let Some(pos) = self.position {
             window.set_position(pos);
         }
 
@@ -463,13 +486,27 @@ pub(crate) struct Window {
     timer_queue: Mutex<BinaryHeap<Timer<()>>>,
     idle_queue: Arc<Mutex<Vec<IdleKind>>>,
     // Writing to this wakes up the event loop, so that it can run idle handlers.
-    idle_pipe: RawFd,
+    idle_pipe: Arc<IdlePipe>,
     next_text_field: Binding<Option<TextFieldToken>>,
     active_text_field: Binding<Option<TextFieldToken>>,
     need_to_reset_compose: Binding<bool>,
+    /// The button which was pressed on this window and is still held, if any
+    held_button: Binding<Option<HeldButton>>,
+    /// The state from the latest `_NET_WM_STATE`, which we have told the handler about
+    window_state: Binding<window::WindowState>,
     parent: Option<Arc<Window>>,
 }
 
+/// A pointer button which is held, giving us an implicit grab of its device
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct HeldButton {
+    device: xinput::DeviceId,
+    button: u32,
+}
+
+/// The `direction` of a `_NET_WM_MOVERESIZE` message which moves the window
+const MOVERESIZE_MOVE: u32 = 8;
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct CustomCursor(xproto::Cursor);
 
@@ -547,6 +584,21 @@ impl Window {
         Ok(())
     }
 
+    /// The user changed their DPI settings, so use `scale` from now on. The window keeps
+    /// its size in pixels, so its size in display points changes.
+    pub(crate) fn handle_scale_changed(&self, scale: Scale) -> Result<(), Error> {
+        if scale == self.scale.get() {
+            return Ok(());
+        }
+        let size = self.area.get().size_px();
+        self.scale.set(scale);
+        self.area.set(ScaledArea::from_px(size, scale));
+        self.add_invalid_rect(size.to_dp(scale).to_rect())?;
+        self.with_handler(|h| h.scale(scale));
+        self.with_handler(|h| h.size(size.to_dp(scale)));
+        Ok(())
+    }
+
     fn render(&self) -> Result<(), Error> {
         self.with_handler(|h| h.prepare_paint());
 
@@ -581,8 +633,54 @@ impl Window {
     }
 
     /// Set whether the window should show titlebar
-    fn show_titlebar(&self, _show_titlebar: bool) {
-        warn!("Window::show_titlebar is currently unimplemented for X11 backend.");
+    fn show_titlebar(&self, show_titlebar: bool) {
+        if self.destroyed() {
+            return;
+        }
+        // There is no standard way to do this, but almost all window managers follow the hints
+        // of the Motif toolkit. These are the flags, functions, decorations, input mode and
+        // status, where a flag of 2 means that only the decorations are given.
+        let hints = [2, 0, u32::from(show_titlebar), 0, 0];
+        let atoms = self.app.atoms();
+        log_x11!(self.app.connection().change_property32(
+            PropMode::REPLACE,
+            self.id,
+            atoms._MOTIF_WM_HINTS,
+            atoms._MOTIF_WM_HINTS,
+            &hints,
+        ));
+    }
+
+    /// The size of the decorations the window manager draws around the window, which is
+    /// zero until the window is mapped
+    fn frame_extents(&self) -> Result<Insets, Error> {
+        let atoms = self.app.atoms();
+        let reply = self
+            .app
+            .connection()
+            .get_property(
+                false,
+                self.id,
+                atoms._NET_FRAME_EXTENTS,
+                AtomEnum::CARDINAL,
+                0,
+                4,
+            )?
+            .reply()?;
+        let Some(extents) = reply.value32() else {
+            return Ok(Insets::ZERO);
+        };
+        let extents = extents.map(f64::from).collect::<Vec<_>>();
+        let &[left, right, top, bottom] = extents.as_slice() else {
+            return Ok(Insets::ZERO);
+        };
+        let scale = self.scale.get();
+        Ok(Insets::new(
+            left / scale.x(),
+            top / scale.y(),
+            right / scale.x(),
+            bottom / scale.y(),
+        ))
     }
 
     fn parent_origin(&self) -> Vec2 {
@@ -631,6 +729,190 @@ impl Window {
         ));
     }
 
+    /// Move the window onto `monitor`, so that the window manager makes it fullscreen there
+    fn move_to_monitor(&self, monitor: &Monitor) {
+        // Monitors are in pixels on X11
+        let origin = monitor.virtual_rect().origin();
+        log_x11!(self.app.connection().configure_window(
+            self.id,
+            &ConfigureWindowAux::new()
+                .x(origin.x as i32)
+                .y(origin.y as i32),
+        ));
+    }
+
+    fn set_fullscreen(&self, fullscreen: bool, monitor: Option<&Monitor>) {
+        if self.destroyed() {
+            return;
+        }
+        if let Some(monitor) = monitor {
+            self.move_to_monitor(monitor);
+        }
+        let fullscreen_atom = self.app.atoms()._NET_WM_STATE_FULLSCREEN;
+        log_x11!(self.change_net_wm_state(fullscreen, fullscreen_atom, x11rb::NONE));
+    }
+
+    fn set_maximized(&self, maximized: bool) {
+        if self.destroyed() {
+            return;
+        }
+        let atoms = self.app.atoms();
+        log_x11!(self.change_net_wm_state(
+            maximized,
+            atoms._NET_WM_STATE_MAXIMIZED_VERT,
+            atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+        ));
+    }
+
+    fn minimize(&self) {
+        fn _minimize(window: &Window) -> Result<(), Error> {
+            let conn = window.app.connection();
+            let root = conn.setup().roots[window.app.screen_num()].root;
+            // 3 is `IconicState`
+            let event = xproto::ClientMessageEvent::new(
+                32,
+                window.id,
+                window.app.atoms().WM_CHANGE_STATE,
+                [3, 0, 0, 0, 0],
+            );
+            conn.send_event(
+                false,
+                root,
+                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+                event,
+            )?;
+            conn.flush()?;
+            Ok(())
+        }
+        if self.destroyed() {
+            return;
+        }
+        log_x11!(_minimize(self));
+    }
+
+    fn restore(&self) {
+        if self.destroyed() {
+            return;
+        }
+        if self.window_state.get() == window::WindowState::Minimized {
+            // Mapping an iconified window asks the window manager to bring it back, in the
+            // state it had before it was minimized
+            log_x11!(self.app.connection().map_window(self.id));
+            return;
+        }
+        self.set_fullscreen(false, None);
+        self.set_maximized(false);
+    }
+
+    fn get_window_state(&self) -> window::WindowState {
+        self.window_state.get()
+    }
+
+    /// The window state described by the `_NET_WM_STATE` of the window
+    fn read_window_state(&self) -> Result<window::WindowState, Error> {
+        let atoms = self.app.atoms();
+        let states = self.net_wm_state()?;
+        let state = if states.contains(&atoms._NET_WM_STATE_HIDDEN) {
+            window::WindowState::Minimized
+        } else if states.contains(&atoms._NET_WM_STATE_FULLSCREEN) {
+            window::WindowState::Fullscreen
+        } else if states.contains(&atoms._NET_WM_STATE_MAXIMIZED_VERT)
+            && states.contains(&atoms._NET_WM_STATE_MAXIMIZED_HORZ)
+        {
+            window::WindowState::Maximized
+        } else {
+            window::WindowState::Restored
+        };
+        Ok(state)
+    }
+
+    /// The atoms in the `_NET_WM_STATE` of the window
+    fn net_wm_state(&self) -> Result<Vec<xproto::Atom>, Error> {
+        let atoms = self.app.atoms();
+        let reply = self
+            .app
+            .connection()
+            .get_property(
+                false,
+                self.id,
+                atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        Ok(reply
+            .value32()
+            .map(|states| states.collect())
+            .unwrap_or_default())
+    }
+
+    /// Ask the window manager to add `first` and `second` to the `_NET_WM_STATE` of the
+    /// window, or to remove them if `add` is false. `second` can be `NONE`.
+    fn change_net_wm_state(
+        &self,
+        add: bool,
+        first: xproto::Atom,
+        second: xproto::Atom,
+    ) -> Result<(), Error> {
+        let conn = self.app.connection();
+        let root = conn.setup().roots[self.app.screen_num()].root;
+        // The source indication of 1 means a normal application
+        let data = [u32::from(add), first, second, 1, 0];
+        let event =
+            xproto::ClientMessageEvent::new(32, self.id, self.app.atoms()._NET_WM_STATE, data);
+        conn.send_event(
+            false,
+            root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        )?;
+        conn.flush()?;
+        Ok(())
+    }
+
+    /// Ask the window manager to start moving or resizing the window in `direction`, following
+    /// the pointer button which is currently held.
+    fn move_resize(&self, direction: u32) {
+        fn _move_resize(window: &Window, direction: u32) -> Result<(), Error> {
+            // The window manager can only take over a button which is held
+            let Some(held) = window.held_button.get() else {
+                return Ok(());
+            };
+            let conn = window.app.connection();
+            let pointer = conn.query_pointer(window.id)?.reply()?;
+            // We have an implicit grab of the pointer, which the window manager needs to take
+            conn.xinput_xi_ungrab_device(x11rb::CURRENT_TIME, held.device)?;
+            window.held_button.set(None);
+            // The source indication of 1 means a normal application
+            let data = [
+                pointer.root_x as u32,
+                pointer.root_y as u32,
+                direction,
+                held.button,
+                1,
+            ];
+            let event = xproto::ClientMessageEvent::new(
+                32,
+                window.id,
+                window.app.atoms()._NET_WM_MOVERESIZE,
+                data,
+            );
+            conn.send_event(
+                false,
+                pointer.root,
+                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+                event,
+            )?;
+            conn.flush()?;
+            Ok(())
+        }
+        if self.destroyed() {
+            return;
+        }
+        log_x11!(_move_resize(self, direction));
+    }
+
     /// Bring this window to the front of the window stack and give it focus.
     fn bring_to_front_and_focus(&self) {
         if self.destroyed() {
@@ -680,7 +962,7 @@ impl Window {
     fn request_anim_frame(&self) {
         let idle = IdleHandle {
             queue: Arc::clone(&self.idle_queue),
-            pipe: self.idle_pipe,
+            pipe: Arc::clone(&self.idle_pipe),
         };
         idle.schedule_redraw();
     }
@@ -759,6 +1041,30 @@ impl Window {
         // TODO(x11/menus): implement Window::set_menu (currently a no-op)
     }
 
+    fn show_context_menu(&self, menu: Menu, pos: Point) {
+        let conn = self.app.connection();
+        let scale = self.scale.get();
+        let pos = pos.to_px(scale);
+        let root = conn.setup().roots[self.app.screen_num()].root;
+        let origin = conn
+            .translate_coordinates(self.id, root, pos.x as i16, pos.y as i16)
+            .map_err(Error::from)
+            .and_then(|cookie| Ok(cookie.reply()?));
+        match origin {
+            Ok(origin) => self.app.show_context_menu(
+                self.id,
+                menu,
+                (origin.dst_x.into(), origin.dst_y.into()),
+                scale.x(),
+            ),
+            Err(e) => error!("Failed to find the position of the context menu: {:#}", e),
+        }
+    }
+
+    pub fn handle_menu_command(&self, id: u32) {
+        self.with_handler(|h| h.command(id));
+    }
+
     fn get_scale(&self) -> Result<Scale, Error> {
         Ok(self.scale.get())
     }
@@ -978,6 +1284,10 @@ impl Window {
         pointer_ev.buttons = pointer_ev.buttons.with(pointer_ev.button);
         // TODO: detect the count
         pointer_ev.count = 1;
+        self.held_button.set(Some(HeldButton {
+            device: ev.deviceid,
+            button: ev.detail,
+        }));
         self.with_handler(|h| h.pointer_down(&pointer_ev));
         Ok(())
     }
@@ -987,6 +1297,9 @@ impl Window {
         // The xcb state includes the newly released button, but druid
         // doesn't want it.
         pointer_ev.buttons = pointer_ev.buttons.without(pointer_ev.button);
+        if pointer_ev.buttons.is_empty() {
+            self.held_button.set(None);
+        }
         self.with_handler(|h| h.pointer_up(&pointer_ev));
         Ok(())
     }
@@ -1093,6 +1406,20 @@ impl Window {
         self.with_handler(|h| h.destroy());
     }
 
+    pub fn handle_net_wm_state_changed(&self) {
+        let state = match self.read_window_state() {
+            Ok(state) => state,
+            Err(e) => {
+                error!("Failed to read _NET_WM_STATE: {}", e);
+                return;
+            }
+        };
+        if state != self.window_state.get() {
+            self.window_state.set(state);
+            self.with_handler(|h| h.window_state_changed(state));
+        }
+    }
+
     pub fn handle_configure_notify(&self, event: &ConfigureNotifyEvent) -> Result<(), Error> {
         self.size_changed(Size::new(event.width as f64, event.height as f64))
     }
@@ -1212,7 +1539,7 @@ fn key_mods(mods: KeyButMask) -> Modifiers {
 #[derive(Clone)]
 pub struct IdleHandle {
     queue: Arc<Mutex<Vec<IdleKind>>>,
-    pipe: RawFd,
+    pipe: Arc<IdlePipe>,
 }
 
 pub(crate) enum IdleKind {
@@ -1223,19 +1550,7 @@ pub(crate) enum IdleKind {
 
 impl IdleHandle {
     fn wake(&self) {
-        loop {
-            match nix::unistd::write(self.pipe, &[0]) {
-                Err(nix::errno::Errno::EINTR) => {}
-                Err(nix::errno::Errno::EAGAIN) => {}
-                Err(e) => {
-                    error!("Failed to write to idle pipe: {}", e);
-                    break;
-                }
-                Ok(_) => {
-                    break;
-                }
-            }
-        }
+        self.pipe.wake();
     }
 
     pub(crate) fn schedule_redraw(&self) {
@@ -1332,8 +1647,14 @@ impl WindowHandle {
     }
 
     pub fn content_insets(&self) -> Insets {
-        warn!("WindowHandle::content_insets unimplemented for X11 backend.");
-        Insets::ZERO
+        if let Some(w) = &self.window {
+            let insets = w.frame_extents();
+            log_x11!(&insets);
+            insets.unwrap_or(Insets::ZERO)
+        } else {
+            error!("Window {} has already been dropped", self.id);
+            Insets::ZERO
+        }
     }
 
     pub fn set_size(&self, size: Size) {
@@ -1353,17 +1674,64 @@ impl WindowHandle {
         }
     }
 
-    pub fn set_window_state(&self, _state: window::WindowState) {
-        warn!("WindowHandle::set_window_state is currently unimplemented for X11 backend.");
+    pub fn set_window_state(&self, state: window::WindowState) {
+        let Some(w) = &self.window else {
+            error!("Window {} has already been dropped", self.id);
+            return;
+        };
+        match state {
+            window::WindowState::Maximized => w.set_maximized(true),
+            window::WindowState::Minimized => w.minimize(),
+            window::WindowState::Restored => w.restore(),
+            window::WindowState::Fullscreen => w.set_fullscreen(true, None),
+        }
+    }
+
+    pub fn set_fullscreen(&self, monitor: Option<&Monitor>) {
+        if let Some(w) = &self.window {
+            w.set_fullscreen(true, monitor);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
     }
 
     pub fn get_window_state(&self) -> window::WindowState {
-        warn!("WindowHandle::get_window_state is currently unimplemented for X11 backend.");
-        window::WindowState::Restored
+        if let Some(w) = &self.window {
+            w.get_window_state()
+        } else {
+            error!("Window {} has already been dropped", self.id);
+            window::WindowState::Restored
+        }
+    }
+
+    pub fn handle_titlebar(&self, val: bool) {
+        if !val {
+            return;
+        }
+        if let Some(w) = &self.window {
+            w.move_resize(MOVERESIZE_MOVE);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
     }
 
-    pub fn handle_titlebar(&self, _val: bool) {
-        warn!("WindowHandle::handle_titlebar is currently unimplemented for X11 backend.");
+    pub fn begin_resize(&self, edge: ResizeEdge) {
+        // The directions of `_NET_WM_MOVERESIZE` go clockwise from the top left
+        let direction = match edge {
+            ResizeEdge::TopLeft => 0,
+            ResizeEdge::Top => 1,
+            ResizeEdge::TopRight => 2,
+            ResizeEdge::Right => 3,
+            ResizeEdge::BottomRight => 4,
+            ResizeEdge::Bottom => 5,
+            ResizeEdge::BottomLeft => 6,
+            ResizeEdge::Left => 7,
+        };
+        if let Some(w) = &self.window {
+            w.move_resize(direction);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
     }
 
     pub fn bring_to_front_and_focus(&self) {
@@ -1478,7 +1846,7 @@ impl WindowHandle {
                     let screen = &setup.roots[w.app.screen_num()];
                     match make_cursor(conn, setup.image_byte_order, screen.root, format, desc) {
                         // TODO: We 'leak' the cursor - nothing ever calls render_free_cursor
-                        Ok(cursor) => Some(cursor),
+                        Ok(cursor) => cursor,
                         Err(err) => {
                             error!("Failed to create custom cursor: {:?}", err);
                             None
@@ -1517,15 +1885,18 @@ impl WindowHandle {
         }
     }
 
-    pub fn show_context_menu(&self, _menu: Menu, _pos: Point) {
-        // TODO(x11/menus): implement WindowHandle::show_context_menu
-        warn!("WindowHandle::show_context_menu is currently unimplemented for X11 backend.");
+    pub fn show_context_menu(&self, menu: Menu, pos: Point) {
+        if let Some(w) = &self.window {
+            w.show_context_menu(menu, pos);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
     }
 
     pub fn get_idle_handle(&self) -> Option<IdleHandle> {
         self.window.as_ref().map(|w| IdleHandle {
             queue: Arc::clone(&w.idle_queue),
-            pipe: w.idle_pipe,
+            pipe: Arc::clone(&w.idle_pipe),
         })
     }
 
@@ -1567,12 +1938,66 @@ impl HasDisplayHandle for WindowHandle {
         }
     }
 }
+
 fn make_cursor(
-    _conn: &XCBConnection,
-    _byte_order: X11ImageOrder,
-    _root_window: u32,
-    _argb32_format: Pictformat,
-    _desc: &CursorDesc,
-) -> Result<Cursor, ReplyOrIdError> {
-    Ok(Cursor::Arrow)
+    conn: &XCBConnection,
+    byte_order: X11ImageOrder,
+    root_window: u32,
+    argb32_format: Pictformat,
+    desc: &CursorDesc,
+) -> Result<Option<Cursor>, ReplyOrIdError> {
+    // Check the size first, as a bad size would only be reported as an error in the event loop
+    let (width, height) = (desc.image.width(), desc.image.height());
+    let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+        _ => {
+            warn!("cannot create a custom cursor from a {width}x{height} image");
+            return Ok(None);
+        }
+    };
+    // The hot spot has to be inside the image
+    let hot_x = desc.hot.x.round().clamp(0., f64::from(width - 1)) as u16;
+    let hot_y = desc.hot.y.round().clamp(0., f64::from(height - 1)) as u16;
+
+    // BEGIN: Lots of code just to get the image into a RENDER Picture
+    let pixels = desc
+        .image
+        .to_premultiplied_argb32()
+        .into_iter()
+        .flat_map(|pixel| match byte_order {
+            X11ImageOrder::LSB_FIRST => pixel.to_le_bytes(),
+            _ => pixel.to_be_bytes(),
+        })
+        .collect::<Vec<u8>>();
+    let pixmap = conn.generate_id()?;
+    let gc = conn.generate_id()?;
+    let picture = conn.generate_id()?;
+    conn.create_pixmap(32, pixmap, root_window, width, height)?;
+    conn.create_gc(gc, pixmap, &Default::default())?;
+
+    conn.put_image(
+        xproto::ImageFormat::Z_PIXMAP,
+        pixmap,
+        gc,
+        width,
+        height,
+        0,
+        0,
+        0,
+        32,
+        &pixels,
+    )?;
+    conn.render_create_picture(picture, pixmap, argb32_format, &Default::default())?;
+
+    conn.free_gc(gc)?;
+    conn.free_pixmap(pixmap)?;
+    // End: Lots of code just to get the image into a RENDER Picture
+
+    let cursor = conn.generate_id()?;
+    conn.render_create_cursor(cursor, picture, hot_x, hot_y)?;
+    conn.render_free_picture(picture)?;
+
+    Ok(Some(Cursor::Custom(
+        crate::backend::window::CustomCursor::X11(CustomCursor(cursor)),
+    )))
 }
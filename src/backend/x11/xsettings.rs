@@ -0,0 +1,257 @@
+// Copyright 2023 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Settings from the XSETTINGS manager, which desktop environments use to tell applications
+//! about the user's preferences, such as their DPI.
+//!
+//! See <https://specifications.freedesktop.org/xsettings-spec/xsettings-latest.html>
+
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt as _,
+    DestroyNotifyEvent, EventMask, PropertyNotifyEvent, Window,
+};
+
+use super::application::AppAtoms;
+
+/// The settings of the current XSETTINGS manager, if there is one.
+#[derive(Debug, Default)]
+pub(crate) struct XSettings {
+    /// The `_XSETTINGS_S<screen>` selection, which the manager owns
+    selection: Atom,
+    /// The window of the manager, which holds the settings in a property
+    owner: Window,
+    settings: Settings,
+}
+
+/// The settings we care about
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Settings {
+    /// `Xft/DPI`, which includes the window scaling factor
+    dpi: Option<f64>,
+    /// `Gdk/WindowScalingFactor`
+    window_scale: Option<i32>,
+}
+
+const INTEGER: u8 = 0;
+const STRING: u8 = 1;
+const COLOR: u8 = 2;
+
+impl XSettings {
+    /// Find the XSETTINGS manager of `screen_num`, and read its settings.
+    pub(crate) fn new(
+        conn: &impl Connection,
+        screen_num: usize,
+        atoms: &AppAtoms,
+    ) -> Result<XSettings, ReplyError> {
+        let name = format!("_XSETTINGS_S{screen_num}");
+        let selection = conn.intern_atom(false, name.as_bytes())?.reply()?.atom;
+        let mut xsettings = XSettings {
+            selection,
+            ..Default::default()
+        };
+        xsettings.update(conn, atoms)?;
+        Ok(xsettings)
+    }
+
+    /// Whether `event` announces a new XSETTINGS manager, which is sent to the root window.
+    pub(crate) fn is_new_manager(&self, event: &ClientMessageEvent, atoms: &AppAtoms) -> bool {
+        event.type_ == atoms.MANAGER
+            && event.format == 32
+            && event.data.as_data32()[1] == self.selection
+    }
+
+    /// Whether `event` is the manager changing the settings.
+    pub(crate) fn is_change(&self, event: &PropertyNotifyEvent, atoms: &AppAtoms) -> bool {
+        event.window == self.owner && event.atom == atoms._XSETTINGS_SETTINGS
+    }
+
+    /// Whether `event` is the manager exiting.
+    pub(crate) fn is_destroyed(&self, event: &DestroyNotifyEvent) -> bool {
+        self.owner != x11rb::NONE && event.window == self.owner
+    }
+
+    /// Find the current manager and read its settings. Returns whether they changed.
+    pub(crate) fn update(
+        &mut self,
+        conn: &impl Connection,
+        atoms: &AppAtoms,
+    ) -> Result<bool, ReplyError> {
+        let old = self.settings;
+        self.owner = conn.get_selection_owner(self.selection)?.reply()?.owner;
+        self.settings = Settings::default();
+        if self.owner == x11rb::NONE {
+            return Ok(self.settings != old);
+        }
+
+        // The manager might exit at any time, which isn't an error for us. This needs to be
+        // checked, so that the error doesn't come back through the event loop.
+        let aux = ChangeWindowAttributesAux::new()
+            .event_mask(EventMask::PROPERTY_CHANGE | EventMask::STRUCTURE_NOTIFY);
+        let watch = conn.change_window_attributes(self.owner, &aux)?.check();
+        let property = match watch {
+            Ok(()) => conn
+                .get_property(
+                    false,
+                    self.owner,
+                    atoms._XSETTINGS_SETTINGS,
+                    AtomEnum::ANY,
+                    0,
+                    u32::MAX,
+                )?
+                .reply(),
+            Err(err) => Err(err),
+        };
+        match property {
+            Ok(property) => match parse(&property.value) {
+                Some(settings) => self.settings = settings,
+                None => tracing::warn!("the XSETTINGS manager's settings are malformed"),
+            },
+            Err(ReplyError::X11Error(_)) => self.owner = x11rb::NONE,
+            Err(err) => return Err(err),
+        }
+        Ok(self.settings != old)
+    }
+
+    /// The DPI the user has chosen, if the manager says.
+    pub(crate) fn dpi(&self) -> Option<f64> {
+        self.settings.dpi.or_else(|| {
+            self.settings
+                .window_scale
+                .map(|scale| 96. * f64::from(scale))
+        })
+    }
+}
+
+/// The settings in the `_XSETTINGS_SETTINGS` property, or `None` if it is malformed.
+fn parse(data: &[u8]) -> Option<Settings> {
+    let mut reader = Reader {
+        data,
+        big_endian: *data.first()? != 0,
+    };
+    reader.take(4)?;
+    let _serial = reader.u32()?;
+    let count = reader.u32()?;
+
+    let mut settings = Settings::default();
+    for _ in 0..count {
+        let kind = reader.take(2)?[0];
+        let name_len = reader.u16()?;
+        let name = reader.take_padded(name_len.into())?;
+        let _last_change_serial = reader.u32()?;
+        match kind {
+            INTEGER => {
+                let value = reader.u32()? as i32;
+                match name {
+                    // Xft/DPI is in 1024ths of a dot per inch
+                    b"Xft/DPI" if value > 0 => settings.dpi = Some(f64::from(value) / 1024.),
+                    b"Gdk/WindowScalingFactor" if value > 0 => {
+                        settings.window_scale = Some(value);
+                    }
+                    _ => {}
+                }
+            }
+            STRING => {
+                let len = reader.u32()?;
+                reader.take_padded(len as usize)?;
+            }
+            COLOR => {
+                reader.take(8)?;
+            }
+            _ => return None,
+        }
+    }
+    Some(settings)
+}
+
+/// Reads the fields of the settings in the byte order of the manager
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(taken)
+    }
+
+    /// Take `len` bytes, and skip the padding to the next multiple of 4 after them.
+    fn take_padded(&mut self, len: usize) -> Option<&'a [u8]> {
+        let taken = self.take(len)?;
+        self.take((4 - len % 4) % 4)?;
+        Some(taken)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.take(2)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.take(4)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Settings};
+    use test_log::test;
+
+    #[test]
+    fn parse_settings() {
+        let mut data = vec![0, 0, 0, 0];
+        data.extend(7u32.to_le_bytes());
+        data.extend(3u32.to_le_bytes());
+        // A string, which is skipped
+        data.extend([1, 0]);
+        data.extend(13u16.to_le_bytes());
+        data.extend(b"Net/ThemeName\0\0\0");
+        data.extend(0u32.to_le_bytes());
+        data.extend(7u32.to_le_bytes());
+        data.extend(b"Adwaita\0");
+        data.extend([0, 0]);
+        data.extend(7u16.to_le_bytes());
+        data.extend(b"Xft/DPI\0");
+        data.extend(0u32.to_le_bytes());
+        data.extend((192 * 1024u32).to_le_bytes());
+        data.extend([0, 0]);
+        data.extend(23u16.to_le_bytes());
+        data.extend(b"Gdk/WindowScalingFactor\0");
+        data.extend(0u32.to_le_bytes());
+        data.extend(2u32.to_le_bytes());
+
+        let settings = Settings {
+            dpi: Some(192.),
+            window_scale: Some(2),
+        };
+        assert_eq!(parse(&data), Some(settings));
+        // Settings which are cut off are malformed
+        assert_eq!(parse(&data[..data.len() - 2]), None);
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright 2023 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A simple image type, used for platform resources such as custom cursors.
+
+use std::sync::Arc;
+
+/// How the alpha channel of an [`ImageBuf`] relates to its colour channels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// The colour channels have already been multiplied by the alpha channel.
+    Premultiplied,
+    /// The colour channels are independent of the alpha channel.
+    Straight,
+}
+
+/// An image with 8-bit RGBA pixels.
+///
+/// The pixel data is reference counted, so cloning an `ImageBuf` is cheap.
+#[derive(Clone)]
+pub struct ImageBuf {
+    pixels: Arc<[u8]>,
+    width: usize,
+    height: usize,
+    stride: usize,
+    alpha_mode: AlphaMode,
+}
+
+impl ImageBuf {
+    /// Create an image from tightly packed RGBA pixels, in rows from top to bottom.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` is shorter than `width * height * 4` bytes.
+    pub fn from_raw(
+        pixels: impl Into<Arc<[u8]>>,
+        alpha_mode: AlphaMode,
+        width: usize,
+        height: usize,
+    ) -> ImageBuf {
+        ImageBuf::from_raw_with_stride(pixels, alpha_mode, width, height, width * 4)
+    }
+
+    /// Create an image from RGBA pixels, where each row starts `stride` bytes after the
+    /// start of the previous row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is less than `width * 4`, or if `pixels` is too short to
+    /// contain `height` rows.
+    pub fn from_raw_with_stride(
+        pixels: impl Into<Arc<[u8]>>,
+        alpha_mode: AlphaMode,
+        width: usize,
+        height: usize,
+        stride: usize,
+    ) -> ImageBuf {
+        let pixels = pixels.into();
+        assert!(
+            stride >= width * 4,
+            "the stride of an image must be at least 4 bytes per pixel"
+        );
+        let required = if height == 0 {
+            0
+        } else {
+            stride * (height - 1) + width * 4
+        };
+        assert!(
+            pixels.len() >= required,
+            "expected at least {required} bytes of pixel data, got {}",
+            pixels.len()
+        );
+        ImageBuf {
+            pixels,
+            width,
+            height,
+            stride,
+            alpha_mode,
+        }
+    }
+
+    /// The width of the image, in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the image, in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The number of bytes between the start of each row.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Whether the colour channels of the image are premultiplied by its alpha channel.
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// The raw RGBA pixel data, which is laid out as described by [`ImageBuf::stride`].
+    pub fn raw_pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// The pixels of each row, from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        (0..self.height).map(|row| &self.pixels[row * self.stride..][..self.width * 4])
+    }
+
+    /// The pixels as premultiplied `0xAARRGGBB` values, in rows from top to bottom
+    /// with no padding.
+    ///
+    /// This is the pixel format which both X11 (with RENDER) and Wayland use for cursors.
+    #[allow(dead_code)] // Not yet used on all platforms.
+    pub(crate) fn to_premultiplied_argb32(&self) -> Vec<u32> {
+        let mut argb = Vec::with_capacity(self.width * self.height);
+        for row in self.rows() {
+            for pixel in row.chunks_exact(4) {
+                let &[r, g, b, a] = pixel else {
+                    unreachable!("chunks are of length 4");
+                };
+                let (r, g, b) = match self.alpha_mode {
+                    AlphaMode::Premultiplied => (r, g, b),
+                    AlphaMode::Straight => (
+                        multiply_alpha(r, a),
+                        multiply_alpha(g, a),
+                        multiply_alpha(b, a),
+                    ),
+                };
+                argb.push(u32::from_be_bytes([a, r, g, b]));
+            }
+        }
+        argb
+    }
+}
+
+impl std::fmt::Debug for ImageBuf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImageBuf")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("stride", &self.stride)
+            .field("alpha_mode", &self.alpha_mode)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Multiply `color` by `alpha`, treating both as fractions of 255, with correct rounding
+fn multiply_alpha(color: u8, alpha: u8) -> u8 {
+    let temp = u16::from(color) * u16::from(alpha) + 0x80;
+    ((temp + (temp >> 8)) >> 8) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn premultiplied_argb32() {
+        #[rustfmt::skip]
+        let pixels: Vec<u8> = vec![
+            255, 0, 0, 255,    0, 255, 0, 128,
+            // Padding, which should be skipped
+            9, 9,
+            0, 0, 255, 0,      10, 20, 30, 255,
+        ];
+        let image = ImageBuf::from_raw_with_stride(pixels, AlphaMode::Straight, 2, 2, 10);
+        assert_eq!(
+            image.to_premultiplied_argb32(),
+            vec![0xffff0000, 0x80008000, 0x00000000, 0xff0a141e]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_short() {
+        ImageBuf::from_raw(vec![0; 15], AlphaMode::Premultiplied, 2, 2);
+    }
+}
@@ -50,6 +50,7 @@ mod common_util;
 mod dialog;
 mod error;
 mod hotkey;
+mod image;
 mod keyboard;
 mod menu;
 mod mouse;
@@ -68,6 +69,7 @@ pub use common_util::Counter;
 pub use dialog::{FileDialogOptions, FileInfo, FileSpec};
 pub use error::Error;
 pub use hotkey::{HotKey, RawMods, SysMods};
+pub use image::{AlphaMode, ImageBuf};
 pub use keyboard::{Code, IntoKey, KbKey, KeyEvent, KeyState, Location, Modifiers};
 pub use menu::Menu;
 pub use mouse::{Cursor, CursorDesc};
@@ -79,8 +81,8 @@ pub use region::Region;
 pub use scale::{Scalable, Scale, ScaledArea};
 pub use screen::{Monitor, Screen};
 pub use window::{
-    FileDialogToken, IdleHandle, IdleToken, TextFieldToken, TimerToken, WinHandler, WindowBuilder,
-    WindowHandle, WindowLevel, WindowState,
+    FileDialogToken, IdleHandle, IdleToken, ResizeEdge, TextFieldToken, TimerToken, WinHandler,
+    WindowBuilder, WindowHandle, WindowLevel, WindowState,
 };
 
 pub use keyboard_types;
@@ -15,8 +15,8 @@
 //! Common types for representing mouse cursor state
 
 use crate::backend;
+use crate::image::ImageBuf;
 use crate::kurbo::Point;
-// use crate::piet::ImageBuf;
 
 //NOTE: this currently only contains cursors that are included by default on
 //both Windows and macOS. We may want to provide polyfills for various additional cursors.
@@ -41,10 +41,10 @@ pub enum Cursor {
 }
 
 /// A platform-independent description of a custom cursor.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct CursorDesc {
-    // #[allow(dead_code)] // Not yet used on all platforms.
-    // pub(crate) image: ImageBuf,
+    #[allow(dead_code)] // Not yet used on all platforms.
+    pub(crate) image: ImageBuf,
     #[allow(dead_code)] // Not yet used on all platforms.
     pub(crate) hot: Point,
 }
@@ -56,12 +56,9 @@ impl CursorDesc {
     /// `(0, 0)` at the top left. The hot spot is the logical position of the mouse cursor within
     /// the image. For example, if the image is a picture of a arrow, the hot spot might be the
     /// coordinates of the arrow's tip.
-    pub fn new(
-        //image: ImageBuf,
-        hot: impl Into<Point>,
-    ) -> CursorDesc {
+    pub fn new(image: ImageBuf, hot: impl Into<Point>) -> CursorDesc {
         CursorDesc {
-            //image,
+            image,
             hot: hot.into(),
         }
     }
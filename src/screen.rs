@@ -15,7 +15,8 @@
 //! Module to get information about monitors
 
 use crate::backend;
-use crate::kurbo::Rect;
+use crate::kurbo::{Rect, Size};
+use crate::Scale;
 use std::fmt;
 use std::fmt::Display;
 
@@ -30,8 +31,12 @@ pub struct Monitor {
     // https://developer.apple.com/documentation/appkit/nsscreen/1388369-visibleframe
     // https://developer.gnome.org/gdk3/stable/GdkMonitor.html#gdk-monitor-get-workarea
     // https://docs.microsoft.com/en-us/windows/win32/api/winuser/ns-winuser-monitorinfo
-    // Unsure about x11
     work_rect: Rect,
+    outputs: Vec<String>,
+    name: Option<String>,
+    refresh_rate: Option<u32>,
+    physical_size: Option<Size>,
+    scale: Scale,
 }
 
 impl Monitor {
@@ -41,8 +46,92 @@ impl Monitor {
             primary,
             rect,
             work_rect,
+            outputs: Vec::new(),
+            name: None,
+            refresh_rate: None,
+            physical_size: None,
+            scale: Scale::default(),
         }
     }
+
+    /// Set the work rectangle of the monitor.
+    #[cfg_attr(
+        not(all(
+            feature = "x11",
+            any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn with_work_rect(mut self, work_rect: Rect) -> Self {
+        self.work_rect = work_rect;
+        self
+    }
+
+    /// Set the names of the outputs showing the monitor.
+    #[cfg_attr(
+        not(all(
+            any(feature = "x11", feature = "wayland"),
+            any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn with_outputs(mut self, outputs: Vec<String>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    /// Set the name of the monitor.
+    #[cfg_attr(
+        not(all(
+            any(feature = "x11", feature = "wayland"),
+            any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Set the refresh rate of the monitor, in millihertz.
+    #[cfg_attr(
+        not(all(
+            any(feature = "x11", feature = "wayland"),
+            any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn with_refresh_rate(mut self, refresh_rate: Option<u32>) -> Self {
+        self.refresh_rate = refresh_rate.filter(|&rate| rate > 0);
+        self
+    }
+
+    /// Set the physical size of the monitor, in millimetres.
+    #[cfg_attr(
+        not(all(
+            any(feature = "x11", feature = "wayland"),
+            any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn with_physical_size(mut self, physical_size: Option<Size>) -> Self {
+        self.physical_size = physical_size.filter(|size| size.width > 0. && size.height > 0.);
+        self
+    }
+
+    /// Set the scale which windows on the monitor should use.
+    #[cfg_attr(
+        not(all(
+            any(feature = "x11", feature = "wayland"),
+            any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn with_scale(mut self, scale: Scale) -> Self {
+        self.scale = scale;
+        self
+    }
+
     /// Returns true if the monitor is the primary monitor.
     /// The primary monitor has its origin at (0, 0) in virtual screen coordinates.
     pub fn is_primary(&self) -> bool {
@@ -59,6 +148,44 @@ impl Monitor {
     pub fn virtual_work_rect(&self) -> Rect {
         self.work_rect
     }
+
+    /// Returns the names of the outputs showing this monitor, such as `HDMI-1`.
+    ///
+    /// There is more than one output if the monitor is mirrored, such as onto a projector.
+    /// This is only known on Linux, and is empty elsewhere.
+    pub fn outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    /// Returns the name of the monitor, such as the name of its connector (`DP-1`) or its
+    /// model, if known.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the refresh rate of the monitor in millihertz, if known.
+    ///
+    /// Divide by 1000 to get the refresh rate in hertz.
+    pub fn refresh_rate(&self) -> Option<u32> {
+        self.refresh_rate
+    }
+
+    /// Returns the physical size of the monitor in millimetres, if known.
+    ///
+    /// This is unknown for some displays, such as projectors, whose size depends on how
+    /// they are set up.
+    pub fn physical_size(&self) -> Option<Size> {
+        self.physical_size
+    }
+
+    /// Returns the scale which the platform prefers windows on this monitor to use.
+    ///
+    /// This is only known on Linux, and is `1.0` elsewhere. Use
+    /// [`WindowHandle::get_scale`](crate::WindowHandle::get_scale) for the scale a window
+    /// actually uses.
+    pub fn scale(&self) -> Scale {
+        self.scale
+    }
 }
 
 impl Display for Monitor {
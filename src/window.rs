@@ -29,6 +29,7 @@ use crate::menu::Menu;
 use crate::mouse::{Cursor, CursorDesc};
 use crate::region::Region;
 use crate::scale::Scale;
+use crate::screen::Monitor;
 use crate::text::{Event, InputHandler};
 use crate::PointerEvent;
 
@@ -178,6 +179,26 @@ pub enum WindowState {
     Maximized,
     Minimized,
     Restored,
+    /// Covering the whole of a monitor, without decorations.
+    ///
+    /// The platform picks the monitor, which is usually the one the window is currently on.
+    /// Use [`WindowHandle::set_fullscreen`] or [`WindowBuilder::fullscreen`] to choose it.
+    ///
+    /// This is currently only supported on Linux
+    Fullscreen,
+}
+
+/// An edge or corner of a window, which can be dragged to resize it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeEdge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
 }
 
 /// A handle to a platform window object.
@@ -218,6 +239,14 @@ impl WindowHandle {
         self.0.set_window_state(state);
     }
 
+    /// Makes the window fullscreen on `monitor`.
+    ///
+    /// If `monitor` is `None`, this is the same as setting the state to
+    /// [`WindowState::Fullscreen`].
+    pub fn set_fullscreen(&mut self, monitor: Option<&Monitor>) {
+        self.0.set_fullscreen(monitor);
+    }
+
     /// Gets the state of the window.
     pub fn get_window_state(&self) -> WindowState {
         self.0.get_window_state()
@@ -228,11 +257,24 @@ impl WindowHandle {
     /// because this refers to the current location of the mouse, you should probably call this
     /// function in response to every relevant [`WinHandler::pointer_move`].
     ///
-    /// This is currently only implemented on Windows
+    /// On Linux, this starts moving the window if a mouse button is held, and does nothing
+    /// otherwise.
+    ///
+    /// This is currently only implemented on Windows and Linux
     pub fn handle_titlebar(&self, val: bool) {
         self.0.handle_titlebar(val);
     }
 
+    /// Start resizing the window from `edge`, following the mouse, as if the user had
+    /// dragged the window's border. This can be used to implement custom window borders,
+    /// and should be called in response to a [`WinHandler::pointer_down`] or
+    /// [`WinHandler::pointer_move`] whilst a mouse button is held.
+    ///
+    /// This is currently only implemented on Linux
+    pub fn begin_resize(&self, edge: ResizeEdge) {
+        self.0.begin_resize(edge);
+    }
+
     /// Set whether the window should show titlebar.
     pub fn show_titlebar(&self, show_titlebar: bool) {
         self.0.show_titlebar(show_titlebar);
@@ -549,6 +591,15 @@ impl WindowBuilder {
         self
     }
 
+    /// Starts the window fullscreen on `monitor`.
+    ///
+    /// If `monitor` is `None`, this is the same as an initial state of
+    /// [`WindowState::Fullscreen`].
+    pub fn fullscreen(mut self, monitor: Option<&Monitor>) -> Self {
+        self.0 = self.0.fullscreen(monitor);
+        self
+    }
+
     /// Attempt to construct the platform window.
     ///
     /// If this fails, your application should exit.
@@ -580,6 +631,13 @@ pub trait WinHandler: Send + Sync {
     #[allow(unused_variables)]
     fn scale(&mut self, scale: Scale) {}
 
+    /// Called when the platform changes the [state](WindowState) of the window, such as when
+    /// the user maximizes it using the titlebar.
+    ///
+    /// This is currently only called on Linux
+    #[allow(unused_variables)]
+    fn window_state_changed(&mut self, state: WindowState) {}
+
     /// Request the handler to prepare to paint the window contents.  In particular, if there are
     /// any regions that need to be repainted on the next call to `paint`, the handler should
     /// invalidate those regions by calling [`WindowHandle::invalidate_rect`] or